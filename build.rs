@@ -22,6 +22,13 @@ pub enum Theme {{
 {}
 }}
 
+pub struct Lang {{
+    pub name: &'static str,
+    pub scope_name: &'static str,
+    pub file_types: Vec<&'static str>,
+    pub first_line_match: Option<&'static str>,
+}}
+
 {}
 
 lazy_static! {{
@@ -30,12 +37,18 @@ lazy_static! {{
 {}
         m
     }};
+
+    pub static ref GLOB_LANG_MAP: Vec<(&'static str, &'static str)> = {{
+        let mut v = Vec::new();
+{}
+        v
+    }};
 }}
 
-pub fn retrieve_syntax(lang: &str) -> &'static str {{
+pub fn retrieve_syntax(lang: &str) -> Option<&'static str> {{
     match lang {{
 {}
-        _ => panic!(\"undefined language: {{}}\", lang),
+        _ => None,
     }}
 }}
 
@@ -58,7 +71,7 @@ pub fn _load_theme(theme: Theme) -> Result<StyleTree> {{
 
 
 lazy_static! {{
-    pub static ref LANGS: Vec<String> = {{
+    pub static ref LANGS: Vec<Lang> = {{
         let mut v = Vec::new();
 {}
 
@@ -88,6 +101,7 @@ fn main() {
     let config = load_config().unwrap();
     let mut raw = String::new();
     let mut ext = String::new();
+    let mut glob = String::new();
     let mut lg = String::new();
     let mut func = String::new();
     let mut lt = String::new();
@@ -106,17 +120,35 @@ fn main() {
             _raw,
             read_file(&path)
         ));
-        syn_mat.push_str(&format!("        \"{}\" => {},\n", lang.scope_name, _raw));
+        syn_mat.push_str(&format!("        \"{}\" => Some({}),\n", lang.scope_name, _raw));
         lg.push_str(&format!("        \"{}\" => {}(),\n", lang.scope_name, _fn));
         // func.push_str(&format!("{}\n", gen_load_syntax_func(&lang.name)));
 
-        for e in lang.file_types {
-            ext.push_str(&format!(
-                "        m.insert(\"{}\", \"{}\");\n",
-                e, lang.scope_name
-            ));
+        for e in &lang.file_types {
+            if is_glob(e) {
+                glob.push_str(&format!(
+                    "        v.push((\"{}\", \"{}\"));\n",
+                    e, lang.scope_name
+                ));
+            } else {
+                ext.push_str(&format!(
+                    "        m.insert(\"{}\", \"{}\");\n",
+                    e, lang.scope_name
+                ));
+            }
         }
-        langs.push_str(&format!("       v.push(\"{}\".to_owned());\n", lang.name));
+        let file_types: Vec<_> = lang.file_types
+            .iter()
+            .map(|e| format!("\"{}\"", e))
+            .collect();
+        langs.push_str(&format!(
+            "       v.push(Lang {{ name: \"{}\", scope_name: \"{}\", file_types: vec![{}], \
+             first_line_match: {:?} }});\n",
+            lang.name,
+            lang.scope_name,
+            file_types.join(", "),
+            lang.first_line_match
+        ));
     }
 
     for theme in config.themes {
@@ -142,6 +174,7 @@ fn main() {
         theme_def,
         raw,
         ext,
+        glob,
         syn_mat,
         lg,
         lt,
@@ -163,6 +196,7 @@ struct Language {
     name: String,
     scope_name: String,
     file_types: Vec<String>,
+    first_line_match: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -193,6 +227,12 @@ fn read_file(path: &str) -> String {
         .replace("\n", "")
 }
 
+// file types are either names, which match a whole file name or the part
+// following a dot, or glob patterns matching a whole file name.
+fn is_glob(file_type: &str) -> bool {
+    file_type.contains(|c| c == '*' || c == '?' || c == '[')
+}
+
 fn raw_syntax_name(lang: &str) -> String {
     format!("RAW_{}_SYNTAX", lang.to_uppercase())
 }
@@ -1,5 +1,9 @@
 use clap::{App, Arg};
 
+use encoding;
+use lang;
+use range;
+
 const ABOUT: &str = "civet colorizes your inputs";
 
 pub fn initialize() -> App<'static, 'static> {
@@ -7,38 +11,260 @@ pub fn initialize() -> App<'static, 'static> {
         .version(crate_version!())
         .author(crate_authors!())
         .about(ABOUT)
+        .arg(
+            Arg::with_name("show-all")
+                .short("A")
+                .long("show-all")
+                .help("equivalent to -vET"),
+        )
         .arg(
             Arg::with_name("number-nonblank")
                 .short("b")
-                .help("number non-blank output lines"),
+                .long("number-nonblank")
+                .help("number non-blank output lines, overrides -n"),
+        )
+        .arg(
+            Arg::with_name("e")
+                .short("e")
+                .help("equivalent to -vE"),
+        )
+        .arg(
+            Arg::with_name("show-ends")
+                .short("E")
+                .long("show-ends")
+                .help("display $ at end of each line"),
         )
         .arg(
             Arg::with_name("number")
                 .short("n")
+                .long("number")
                 .help("number all output lines"),
         )
         .arg(
             Arg::with_name("squeeze-blank")
                 .short("s")
+                .long("squeeze-blank")
                 .help("squeeze multiple blank line into one"),
         )
         .arg(
-            Arg::with_name("raw-control-chars")
-                .short("r")
-                .help("output raw control characters"),
+            Arg::with_name("t")
+                .short("t")
+                .help("equivalent to -vT"),
+        )
+        .arg(
+            Arg::with_name("show-tabs")
+                .short("T")
+                .long("show-tabs")
+                .help("display TAB characters as ^I"),
+        )
+        .arg(
+            Arg::with_name("u")
+                .short("u")
+                .help("(ignored)"),
+        )
+        .arg(
+            Arg::with_name("show-nonprinting")
+                .short("v")
+                .long("show-nonprinting")
+                .help("use ^ and M- notation, except for LFD and TAB"),
+        )
+        .arg(
+            Arg::with_name("line-range")
+                .value_name("N:M")
+                .long("line-range")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(|v| range::parse(&v).map(|_| ()))
+                .help("print only lines N to M, either end can be left open"),
+        )
+        .arg(
+            Arg::with_name("head")
+                .value_name("N")
+                .long("head")
+                .validator(is_number)
+                .help("print only the first N lines"),
+        )
+        .arg(
+            Arg::with_name("tail")
+                .value_name("N")
+                .long("tail")
+                .validator(is_number)
+                .help("print only the last N lines"),
+        )
+        .arg(
+            Arg::with_name("highlight-line")
+                .value_name("N:M")
+                .long("highlight-line")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(|v| range::parse(&v).map(|_| ()))
+                .help("paint lines N to M with the line highlight color of the theme"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .value_name("when")
+                .long("color")
+                .possible_values(&["auto", "always", "never"])
+                .default_value("auto")
+                .help("when to colorize the output, auto respects NO_COLOR and CLICOLOR_FORCE"),
+        )
+        .arg(
+            Arg::with_name("follow")
+                .short("f")
+                .long("follow")
+                .help("print the last lines of a file and then lines appended to it"),
+        )
+        .arg(
+            Arg::with_name("diff")
+                .value_names(&["old", "new"])
+                .long("diff")
+                .number_of_values(2)
+                .conflicts_with_all(&["file", "follow"])
+                .help("print a unified diff of two files, highlighting both of them"),
+        )
+        .arg(
+            Arg::with_name("side-by-side")
+                .value_names(&["left", "right"])
+                .long("side-by-side")
+                .number_of_values(2)
+                .conflicts_with_all(&["file", "follow", "diff"])
+                .help("print two files in two columns with their changes side by side"),
+        )
+        .arg(
+            Arg::with_name("recursive")
+                .short("R")
+                .long("recursive")
+                .help("read files under directories, except those ignored by .gitignore \
+                       and .ignore"),
+        )
+        .arg(
+            Arg::with_name("include")
+                .value_name("glob")
+                .long("include")
+                .multiple(true)
+                .number_of_values(1)
+                .help("read only files matching the glob in directories"),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .value_name("glob")
+                .long("exclude")
+                .multiple(true)
+                .number_of_values(1)
+                .help("leave out files and directories matching the glob"),
+        )
+        .arg(
+            Arg::with_name("style")
+                .value_name("components")
+                .long("style")
+                .use_delimiter(true)
+                .possible_values(&["header", "numbers", "changes", "grid", "plain"])
+                .help("decorate files with a header, a gutter of line numbers and git \
+                       changes and a grid, or none of them with plain"),
+        )
+        .arg(
+            Arg::with_name("tabs")
+                .value_name("N")
+                .long("tabs")
+                .validator(is_number)
+                .default_value("0")
+                .help("expand tabs to N spaces, 0 leaves them to the terminal"),
+        )
+        .arg(
+            Arg::with_name("wrap")
+                .value_name("mode")
+                .long("wrap")
+                .possible_values(&["auto", "never", "character"])
+                .default_value("auto")
+                .help("how to wrap long lines at the width of the terminal"),
+        )
+        .arg(
+            Arg::with_name("paging")
+                .value_name("when")
+                .long("paging")
+                .possible_values(&["auto", "always", "never"])
+                .default_value("auto")
+                .help("when to page the output with $CIVET_PAGER, $PAGER or less -RFX"),
+        )
+        .arg(
+            Arg::with_name("language")
+                .value_name("language")
+                .long("language")
+                .short("l")
+                .help("set the language by name, scope name or extension"),
+        )
+        .arg(
+            Arg::with_name("map-syntax")
+                .value_name("glob:language")
+                .long("map-syntax")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(|v| lang::split_mapping(&v).map(|_| ()))
+                .help("use the language for files matching the glob, e.g. '*.conf:toml'"),
+        )
+        .arg(
+            Arg::with_name("file-name")
+                .value_name("name")
+                .long("file-name")
+                .help("detect the language of stdin as if it were read from this file"),
+        )
+        .arg(
+            Arg::with_name("guess")
+                .long("guess")
+                .help("guess the language from the contents when it is not detected"),
+        )
+        .arg(
+            Arg::with_name("list-languages")
+                .long("list-languages")
+                .help("print supported languages and their extensions"),
+        )
+        .arg(
+            Arg::with_name("binary")
+                .value_name("mode")
+                .long("binary")
+                .possible_values(&["print", "skip", "notice"])
+                .default_value("notice")
+                .help("what to do with binary files, a notice of their size by default"),
+        )
+        .arg(
+            Arg::with_name("encoding")
+                .value_name("encoding")
+                .long("encoding")
+                .possible_values(encoding::NAMES)
+                .case_insensitive(true)
+                .help("decode inputs from this encoding, byte order marks are detected otherwise"),
         )
         .arg(
             Arg::with_name("theme")
                 .value_name("theme")
                 .long("theme")
-                .short("t")
                 .help("change color styles"),
         )
         .arg(
             Arg::with_name("supported")
-                .short("v")
                 .long("supported")
                 .help("print supported languages and themes"),
         )
+        .arg(
+            Arg::with_name("config-path")
+                .long("config-path")
+                .help("print the path of the config file"),
+        )
+        .arg(
+            Arg::with_name("no-config")
+                .long("no-config")
+                .help("ignore the config file and CIVET_OPTS"),
+        )
+        .arg(
+            Arg::with_name("debug")
+                .long("debug")
+                .help("print debugging information to stderr"),
+        )
         .arg(Arg::with_name("file").multiple(true))
 }
+
+fn is_number(v: String) -> Result<(), String> {
+    v.parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| format!("invalid number: {}", v))
+}
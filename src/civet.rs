@@ -1,26 +1,50 @@
-use std::{self, result};
-use std::borrow::Cow;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Write};
+use std::{self, cmp, result, thread};
+use std::cell::Cell;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
 use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
 
 use atty;
+use flate2::bufread::MultiGzDecoder;
 
 use app;
 use colorizer::LineColorizer;
+use config::{self, Config, Matches};
+use diff_writer::{DiffWriter, Side};
+use encoding::{Decoder, Encoding};
+use input;
 use lang;
 use theme;
 use error::Error;
+use git;
+use pager::Output;
+use walk::Walker;
+use range::LineRanges;
+use syntax::Grammar;
+use writer::{Size, Writer};
 use _generated;
 
 type Result<T> = result::Result<T, Error>;
 
 static EXECUTABLE_NAME: &'static str = "cv";
 
+/// Number of the last lines of a file written before following it.
+const FOLLOW_LINES: usize = 10;
+
+/// How often a followed file is checked for new lines.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+
 pub struct Civet {
     ll: lang::LangLoader,
     args: Arguments,
     supported: Supported,
+    // whether any input failed, which makes the exit status 1 like cat
+    failed: Cell<bool>,
 }
 
 impl Civet {
@@ -32,101 +56,464 @@ impl Civet {
             ll: lang::LangLoader::new(),
             args,
             supported,
+            failed: Cell::new(false),
         }
     }
 
     pub fn run(self) {
-        let stdout = std::io::stdout();
         if self.args.options.print_supported {
             self.supported.print();
             std::process::exit(0);
         }
+        if self.args.options.list_languages {
+            self.supported.print_languages();
+            std::process::exit(0);
+        }
+        if self.args.options.follow && self.args.file_names.len() > 1 {
+            print_error("only a single file can be followed");
+            std::process::exit(1);
+        }
 
-        for file_name in &self.args.file_names {
-            let mut w = Writer::new(stdout.lock(), &self.args.options);
-            if file_name == "-" {
-                w.copy(std::io::stdin()).unwrap();
-            } else if let Err(e) = self.write_file(&file_name, &mut w) {
-                print_error(&format!("{}: {}", file_name, e))
+        let output = if self.use_pager() {
+            Output::pager(self.args.options.paging == Paging::Always)
+        } else {
+            Output::stdout()
+        };
+        if let Some((compare, ref old, ref new)) = self.args.options.compare {
+            let mut w = DiffWriter::new(output, &self.args.options);
+            self.write_diff(compare, old, new, &mut w);
+            w.into_inner().finish();
+            if self.failed.get() {
+                std::process::exit(1);
+            }
+            return;
+        }
+        let mut w = Writer::new(output, &self.args.options);
+        let walker = Walker::new(&self.args.options.include, &self.args.options.exclude);
+        'files: for file_name in &self.args.file_names {
+            if !(self.args.options.recursive && Path::new(file_name).is_dir()) {
+                if !self.write_file(file_name, &mut w) {
+                    break;
+                }
+                continue;
             }
+            for entry in walker.walk(Path::new(file_name)) {
+                match entry {
+                    Ok(path) => {
+                        if !self.write_file(&path.to_string_lossy(), &mut w) {
+                            break 'files;
+                        }
+                    }
+                    Err(e) => {
+                        print_error(&e.to_string());
+                        self.failed.set(true);
+                    }
+                }
+            }
+        }
+        w.into_inner().finish();
+        if self.failed.get() {
+            std::process::exit(1);
         }
     }
 
-    fn write_file<'a, W: Write, T: AsRef<str>>(
-        &self,
-        file_name: T,
-        writer: &mut Writer<'a, W>,
-    ) -> Result<()> {
-        let options = &self.args.options;
-        let file = File::open(file_name.as_ref())?;
-        let path = Path::new(file_name.as_ref());
-        let grammar = if options.raw_control_chars {
-            path.extension()
-                .and_then(|ext| ext.to_str())
-                .and_then(|ext| lang::identify(ext))
-                .map(|ln| self.ll.load_grammar(ln))
+    /// Writes a file or stdin for `-`, printing errors. Returns false when the
+    /// output cannot be written anymore.
+    fn write_file<'a, W: Write>(&self, file_name: &str, w: &mut Writer<'a, W>) -> bool {
+        let result = if self.args.options.follow && file_name != "-" {
+            self.follow(file_name, w)
+        } else if file_name == "-" {
+            let stdin = std::io::stdin();
+            self.write_stdin(stdin.lock(), w)
         } else {
-            None
+            File::open(file_name).map_err(Error::from).and_then(|mut file| {
+                let tail = if self.needs_detection() {
+                    input::tail_lines(&mut file, lang::MODELINE_LINES)
+                } else {
+                    Vec::new()
+                };
+                let size = file.metadata().ok().map(|m| m.len());
+                self.write_input(Some(Path::new(file_name)), file, &tail, size, w)
+            })
+        };
+        self.report(file_name, result)
+    }
+
+    /// Writes stdin, whose language is detected from `--file-name` as if it
+    /// were the path.
+    fn write_stdin<'a, R: Read, W: Write>(&self, stdin: R, w: &mut Writer<'a, W>) -> Result<()> {
+        let hint = self.args.options.file_name.as_ref().map(Path::new);
+        self.write_input(hint, stdin, &[], None, w)
+    }
+
+    /// Prints the error of an input, or of the output. Returns false when the
+    /// output cannot be written anymore.
+    fn report(&self, file_name: &str, result: Result<()>) -> bool {
+        let output_failed = match result {
+            Ok(()) => return true,
+            // the pager or the reader of a pipe has quit, which is no failure
+            Err(Error::Output(ref e)) if e.kind() == io::ErrorKind::BrokenPipe => return false,
+            Err(e @ Error::Output(_)) => {
+                print_error(&e.to_string());
+                true
+            }
+            Err(e) => {
+                print_error(&format!("{}: {}", file_name, e));
+                false
+            }
         };
+        self.failed.set(true);
+        !output_failed
+    }
 
-        match grammar {
-            Some(g) => {
-                let mut lc = LineColorizer::new(theme::load(options.theme), &g);
-                writer.write(file, |s| Cow::Owned(lc.process_line(s)))
+    /// Writes the differences between two files for `--diff` or
+    /// `--side-by-side`.
+    fn write_diff<W: Write>(
+        &self,
+        compare: Compare,
+        old: &str,
+        new: &str,
+        w: &mut DiffWriter<W>,
+    ) {
+        let old_side = self.read_side(old);
+        let new_side = self.read_side(new);
+        match (old_side, new_side) {
+            (Ok(ref old_side), Ok(ref new_side))
+                if self.args.options.binary == Binary::Skip
+                    && (old_side.is_binary() || new_side.is_binary()) => {}
+            (Ok(old_side), Ok(new_side)) => {
+                let result = match compare {
+                    Compare::Unified => w.write_unified(&old_side, &new_side),
+                    Compare::SideBySide => w.write_side_by_side(&old_side, &new_side),
+                };
+                self.report(new, result);
+            }
+            (old_side, new_side) => {
+                self.report(old, old_side.map(|_| ()));
+                self.report(new, new_side.map(|_| ()));
             }
-            None => writer.copy(file),
         }
     }
-}
 
-struct Writer<'a, W: Write> {
-    inner: W,
-    options: &'a Options,
-}
+    /// Reads a file compared by `--diff` or `--side-by-side`, decoded like the
+    /// inputs of cat and colorized by a tokenizer of its own with the grammar
+    /// of its language. A binary file is kept as it is to be compared whole.
+    fn read_side(&self, file_name: &str) -> Result<Side> {
+        let options = &self.args.options;
+        let path = Path::new(file_name);
+        let mut buf = Vec::new();
+        File::open(path)?.read_to_end(&mut buf)?;
+        let mut inner_path = None;
+        if input::is_gzip(&buf) {
+            let mut inflated = Vec::new();
+            MultiGzDecoder::new(&buf[..]).read_to_end(&mut inflated)?;
+            buf = inflated;
+            inner_path = Some(input::strip_compression(path));
+        }
 
-impl<'a, W: Write> Writer<'a, W> {
-    fn new(inner: W, options: &'a Options) -> Writer<'a, W> {
-        Writer { inner, options }
+        let mut input = &buf[..];
+        let encoding = self.input_encoding(&mut input, true)?;
+        if encoding.is_none() && options.binary != Binary::Print && input::is_binary(input) {
+            return Ok(Side::binary(file_name, &buf));
+        }
+        let mut decoded = Vec::new();
+        Decoder::new(input, encoding.unwrap_or(Encoding::Utf8)).read_to_end(&mut decoded)?;
+        let text = String::from_utf8_lossy(&decoded);
+
+        let detection_path = inner_path.as_ref().map_or(path, |p| p.as_path());
+        let mut tail: Vec<String> = text.lines()
+            .rev()
+            .take(lang::MODELINE_LINES)
+            .map(String::from)
+            .collect();
+        tail.reverse();
+        let mut head = text.as_bytes();
+        let scope = self.detect_language(Some(path), Some(detection_path), &mut head, &tail)?;
+        let grammar = self.load_grammar(file_name, scope);
+        let lc = grammar
+            .as_ref()
+            .map(|g| LineColorizer::new(theme::load(options.theme), g));
+        Ok(Side::new(file_name, &text, lc))
     }
 
-    fn copy<R: Read>(&mut self, r: R) -> Result<()> {
-        self.write(r, |s| Cow::Borrowed(s)).map_err(|e| e.into())
+    /// Decides whether to page the output for the value of `--paging`. In the
+    /// auto mode, the output is paged on a terminal unless stdin is read from
+    /// the terminal too, and the pager is started only when the output is
+    /// taller than the screen.
+    fn use_pager(&self) -> bool {
+        match self.args.options.paging {
+            Paging::Always => true,
+            Paging::Never => false,
+            Paging::Auto => {
+                atty::is(atty::Stream::Stdout) && !self.args.options.follow
+                    && !(self.args.file_names.iter().any(|f| f == "-")
+                        && atty::is(atty::Stream::Stdin))
+            }
+        }
     }
 
-    fn write<R, F>(&mut self, r: R, mut f: F) -> Result<()>
-    where
-        R: Read,
-        F: for<'b> FnMut(&'b str) -> Cow<'b, str>,
-    {
-        let mut line_num = 1;
-        let mut prev_blank = false;
-        let mut reader = BufReader::new(r);
+    fn needs_detection(&self) -> bool {
+        let options = &self.args.options;
+        (options.colored || options.decorations.header) && options.language.is_none()
+    }
 
-        loop {
-            let mut line = String::new();
-            let blank_line = match reader.read_line(&mut line)? {
-                0 => break,
-                _ => line == "\n" || line == "\r\n",
+    fn guess(&self, path: Option<&Path>, head: &[String]) -> Option<&'static str> {
+        let guessed = self.ll.guess(head, &theme::load(self.args.options.theme));
+        if self.args.options.debug {
+            let name = path.map_or("-".into(), |p| p.display().to_string());
+            match guessed {
+                Some((scope, score)) => print_debug(&format!(
+                    "{}: guessed {} with a score of {:.0}%",
+                    name,
+                    lang::name(scope).unwrap_or(scope),
+                    score * 100.0
+                )),
+                None => print_debug(&format!("{}: no language guessed", name)),
+            }
+        }
+        guessed.map(|(scope, _)| scope)
+    }
+
+    /// Writes an input, decompressing gzip, colorized with the grammar of
+    /// `--language` or of the language detected from `path` and the lines at
+    /// both ends of the input. `tail` holds the last lines and `size` the size
+    /// in bytes, which are known only for regular files.
+    /// Inputs are decoded into UTF-8 from `--encoding`, or from the encoding
+    /// of a byte order mark when the output is colorized.
+    fn write_input<'a, R: Read, W: Write>(
+        &self,
+        path: Option<&Path>,
+        input: R,
+        tail: &[String],
+        size: Option<u64>,
+        writer: &mut Writer<'a, W>,
+    ) -> Result<()> {
+        let mut input = BufReader::new(input);
+        if input::is_gzip(input.fill_buf()?) {
+            let inner_path = path.map(input::strip_compression);
+            let input = BufReader::new(MultiGzDecoder::new(input));
+            let detection_path = inner_path.as_deref();
+            let size = size.map(Size::Compressed);
+            return self.write_text(path, detection_path, input, &[], size, writer);
+        }
+        self.write_text(path, path, input, tail, size.map(Size::Bytes), writer)
+    }
+
+    /// Returns the encoding of `--encoding` or of the byte order mark at the
+    /// start of an input, consuming the mark when it is decoded. The mark is
+    /// decoded unless `decode_bom` is false. An input with neither gives
+    /// `None`.
+    fn input_encoding<B: BufRead>(
+        &self,
+        input: &mut B,
+        decode_bom: bool,
+    ) -> io::Result<Option<Encoding>> {
+        let options = &self.args.options;
+        Ok(match (options.encoding, Encoding::from_bom(input.fill_buf()?)) {
+            (Some(enc), Some((bom, len))) if enc == bom => {
+                input.consume(len);
+                Some(enc)
+            }
+            (Some(enc), _) => Some(enc),
+            (None, Some((bom, len))) if decode_bom => {
+                input.consume(len);
+                Some(bom)
+            }
+            (None, Some(_)) => Some(Encoding::Utf8),
+            (None, None) => None,
+        })
+    }
+
+    /// Returns the scope of `--language`, or of the language detected from
+    /// `detection_path` and the lines at both ends of the input.
+    fn detect_language<B: BufRead>(
+        &self,
+        path: Option<&Path>,
+        detection_path: Option<&Path>,
+        input: &mut B,
+        tail: &[String],
+    ) -> io::Result<Option<&'static str>> {
+        let options = &self.args.options;
+        let name = path.map_or("-".into(), |p| p.display().to_string());
+        let scope = if !self.needs_detection() {
+            options.language
+        } else {
+            let n = if options.guess {
+                lang::GUESS_LINES
+            } else {
+                lang::MODELINE_LINES
             };
+            let head = input::peek_lines(input, n)?;
+            let modeline_head = &head[..cmp::min(head.len(), lang::MODELINE_LINES)];
+            let mapping = &options.syntax_mapping;
+            // a grammar with a broken pattern is warned about like one failing to load
+            let detected = lang::detect(detection_path, modeline_head, tail, mapping)
+                .unwrap_or_else(|e| {
+                    print_error(&format!("{}: {}", name, e));
+                    None
+                });
+            match detected {
+                Some(scope) => Some(scope),
+                None if options.guess => self.guess(path, &head),
+                None => None,
+            }
+        };
+        if options.debug {
+            let lang = scope.and_then(lang::name).unwrap_or("none");
+            print_debug(&format!("{}: language {}", name, lang));
+        }
+        Ok(scope)
+    }
+
+    /// Loads the grammar of `scope` when the output is colorized. An input
+    /// whose grammar fails to load is written without colors.
+    fn load_grammar(&self, name: &str, scope: Option<&'static str>) -> Option<Rc<Grammar>> {
+        let scope = scope.filter(|_| self.args.options.colored)?;
+        match self.ll.load_grammar(scope) {
+            Ok(grammar) => Some(grammar),
+            Err(e) => {
+                print_error(&format!("{}: {}, written without colors", name, e));
+                None
+            }
+        }
+    }
+
+    /// Writes a file and then the lines appended to it like `tail -f`. The
+    /// last lines are read from near the end of the file, unless lines are
+    /// selected from the start, and the tokenizer starts there and keeps its
+    /// state between reads. It starts over when the file is truncated or
+    /// replaced by rotation. Only complete lines are written, so a line is
+    /// tokenized once it is appended in full.
+    fn follow<'a, W: Write>(&self, file_name: &str, writer: &mut Writer<'a, W>) -> Result<()> {
+        let options = &self.args.options;
+        let path = Path::new(file_name);
+        let mut file = File::open(path)?;
+        let tail = input::tail_lines(&mut file, lang::MODELINE_LINES);
+        let mut head = BufReader::new(&file);
+        let scope = self.detect_language(Some(path), Some(path), &mut head, &tail)?;
+        let grammar = self.load_grammar(file_name, scope);
+        let new_colorizer = || {
+            grammar
+                .as_ref()
+                .map(|g| LineColorizer::new(theme::load(options.theme), g))
+        };
+        let mut lc = new_colorizer();
+
+        // without ranges, the last lines are written from `start` on, and
+        // the ranges are resolved over the whole file otherwise
+        let start = if options.line_ranges.is_empty() {
+            input::tail_offset(&mut file, FOLLOW_LINES)?
+        } else {
+            0
+        };
+        let size = Some(Size::Bytes(file.metadata()?.len()));
+        let changes = if options.decorations.changes {
+            git::changes(path)
+        } else {
+            git::LineChanges::new()
+        };
+        writer.begin_input(file_name, scope.and_then(lang::name), size, changes)?;
+        let indexed = options.display_number || options.decorations.numbers
+            || options.decorations.changes || !options.highlight_lines.is_empty();
+        if start > 0 && indexed {
+            // the lines before are counted for their numbers, uncolorized
+            file.seek(SeekFrom::Start(0))?;
+            let before = BufReader::new(Read::by_ref(&mut file).take(start));
+            writer.write(before, None, &LineRanges::none())?;
+        }
+        file.seek(SeekFrom::Start(start))?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        let mut pos = start + buf.len() as u64;
+        let mut pending = buf.split_off(complete_len(&buf));
+        let lines = options.line_ranges.with_count(input::count_lines(&buf));
+        writer.write(&buf[..], lc.as_mut(), &lines)?;
+
+        // the writer counts the lines on, so the ranges hold for appended lines
+        loop {
+            thread::sleep(FOLLOW_INTERVAL);
+            pos += file.read_to_end(&mut pending)? as u64;
+            let complete = complete_len(&pending);
+            if complete > 0 {
+                writer.write(&pending[..complete], lc.as_mut(), &lines)?;
+                pending.drain(..complete);
+            }
 
-            if self.options.squeeze_blank && prev_blank && blank_line {
+            // the file may be missing for a moment while it is rotated
+            let metadata = match fs::metadata(path) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if is_replaced(&file, &metadata)? {
+                // the new file may not be created yet, like with `tail -F`
+                file = match File::open(path) {
+                    Ok(file) => file,
+                    Err(_) => continue,
+                };
+                print_error(&format!("{}: file replaced, following the new file", file_name));
+            } else if metadata.len() < pos {
+                print_error(&format!("{}: file truncated", file_name));
+                file.seek(SeekFrom::Start(0))?;
+            } else {
                 continue;
             }
-            prev_blank = blank_line;
+            pos = 0;
+            pending.clear();
+            lc = new_colorizer();
+        }
+    }
 
-            if self.options.display_number {
-                if self.options.number_nonblack && blank_line {
-                    self.inner.write_fmt(format_args!("      \t")).unwrap();
-                } else {
-                    self.inner
-                        .write_fmt(format_args!("{:6}\t", line_num))
-                        .unwrap();
-                    line_num += 1;
+    /// Writes an uncompressed input. The language is detected from
+    /// `detection_path`, which is `path` without compression suffixes.
+    fn write_text<'a, B: BufRead, W: Write>(
+        &self,
+        path: Option<&Path>,
+        detection_path: Option<&Path>,
+        mut input: B,
+        tail: &[String],
+        size: Option<Size>,
+        writer: &mut Writer<'a, W>,
+    ) -> Result<()> {
+        let options = &self.args.options;
+        // plain output keeps the bytes as they are like cat does
+        let encoding = self.input_encoding(&mut input, options.colored)?;
+        if encoding.is_none() && options.binary != Binary::Print
+            && input::is_binary(input.fill_buf()?)
+        {
+            if options.binary == Binary::Notice {
+                // only inputs of unknown size, like pipes, are read to count them
+                let len = match size {
+                    Some(Size::Bytes(len)) => len,
+                    _ => io::copy(&mut input, &mut io::sink())?,
                 };
+                let name = path.map_or("-".into(), |p| p.display().to_string());
+                writer.write_notice(&format!("{}: binary file, {} bytes", name, len))?;
             }
-            self.inner.write_fmt(format_args!("{}", f(&line))).unwrap();
+            return Ok(());
+        }
+        let mut input = Decoder::new(input, encoding.unwrap_or(Encoding::Utf8));
+
+        let scope = self.detect_language(path, detection_path, &mut input, tail)?;
+        let name = path.map_or("-".into(), |p| p.display().to_string());
+        let grammar = self.load_grammar(&name, scope);
+        let mut lc = grammar.map(|g| LineColorizer::new(theme::load(options.theme), &g));
+        // stdin has no version in git even with a file name to detect its language
+        let changes = match path {
+            Some(path) if options.decorations.changes && size.is_some() => git::changes(path),
+            _ => git::LineChanges::new(),
+        };
+
+        if options.line_ranges.needs_count() {
+            let mut buf = Vec::new();
+            input.read_to_end(&mut buf)?;
+            let lines = options.line_ranges.with_count(input::count_lines(&buf));
+            writer.begin_input(&name, scope.and_then(lang::name), size, changes)?;
+            writer.write(&buf[..], lc.as_mut(), &lines)?;
+        } else {
+            writer.begin_input(&name, scope.and_then(lang::name), size, changes)?;
+            writer.write(input, lc.as_mut(), &options.line_ranges)?;
         }
-        self.inner.flush().map_err(|e| e.into())
+        writer.end_input()
     }
 }
 
@@ -136,25 +523,131 @@ struct Arguments {
 }
 
 fn parse_arguments(supported: &Supported) -> Arguments {
-    let matches = app::initialize().get_matches();
+    let cli = app::initialize().get_matches();
+    let config_path = config::path();
+    if cli.occurrences_of("config-path") > 0 {
+        match config_path {
+            Some(path) => println!("{}", path.display()),
+            None => {
+                print_error("no config directory, set XDG_CONFIG_HOME or HOME");
+                std::process::exit(1);
+            }
+        }
+        std::process::exit(0);
+    }
+
+    let mut matches = Matches::new(cli);
+    let mut config = Config::default();
+    if matches.occurrences_of("no-config") == 0 {
+        if let Err(e) = config::env_args().and_then(|args| matches.add_defaults(&args)) {
+            print_error(&format!("CIVET_OPTS: {}", e));
+            std::process::exit(1);
+        }
+        if let Some(path) = config_path {
+            let result = Config::load(&path).and_then(|c| {
+                matches.add_defaults(&c.args()).map_err(Error::Config)?;
+                Ok(c)
+            });
+            match result {
+                Ok(c) => config = c,
+                Err(e) => {
+                    print_error(&format!("{}: {}", path.display(), e));
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    let show_all = matches.occurrences_of("show-all") > 0;
+    let e = matches.occurrences_of("e") > 0;
+    let t = matches.occurrences_of("t") > 0;
 
     let mut options = Options {
         display_number: false,
-        number_nonblack: matches.occurrences_of("number-nonblank") > 0,
+        number_nonblank: matches.occurrences_of("number-nonblank") > 0,
         squeeze_blank: matches.occurrences_of("squeeze-blank") > 0,
-        raw_control_chars: matches.occurrences_of("raw-control-chars") > 0,
+        show_ends: show_all || e || matches.occurrences_of("show-ends") > 0,
+        show_tabs: show_all || t || matches.occurrences_of("show-tabs") > 0,
+        show_nonprinting: show_all || e || t
+            || matches.occurrences_of("show-nonprinting") > 0,
+        colored: use_color(
+            matches.value_of("color").unwrap(),
+            |name| std::env::var_os(name),
+            atty::is(atty::Stream::Stdout),
+        ),
         print_supported: matches.occurrences_of("supported") > 0,
+        list_languages: matches.occurrences_of("list-languages") > 0,
+        language: None,
+        file_name: matches.value_of("file-name").map(String::from),
+        guess: matches.occurrences_of("guess") > 0,
+        encoding: matches.value_of("encoding").and_then(Encoding::from_name),
+        binary: match matches.value_of("binary") {
+            Some("print") => Binary::Print,
+            Some("skip") => Binary::Skip,
+            _ => Binary::Notice,
+        },
+        line_ranges: LineRanges::default(),
+        highlight_lines: LineRanges::default(),
+        decorations: Decorations::default(),
+        follow: matches.occurrences_of("follow") > 0,
+        compare: compared(&matches, "diff", Compare::Unified)
+            .or_else(|| compared(&matches, "side-by-side", Compare::SideBySide)),
+        recursive: matches.occurrences_of("recursive") > 0,
+        include: values(&matches, "include"),
+        exclude: values(&matches, "exclude"),
+        tabs: matches.value_of("tabs").unwrap().parse().unwrap(),
+        wrap: match matches.value_of("wrap").unwrap() {
+            "never" => Wrap::Never,
+            "character" => Wrap::Character,
+            _ => Wrap::Auto,
+        },
+        paging: match matches.value_of("paging").unwrap() {
+            "always" => Paging::Always,
+            "never" => Paging::Never,
+            _ => Paging::Auto,
+        },
+        syntax_mapping: lang::SyntaxMapping::default(),
+        debug: matches.occurrences_of("debug") > 0,
 
         theme: theme::default(),
     };
 
+    if let Some(ranges) = matches.values_of("line-range") {
+        for range in ranges {
+            options.line_ranges.add(range).unwrap();
+        }
+    }
+    if let Some(ranges) = matches.values_of("highlight-line") {
+        for range in ranges {
+            options.highlight_lines.add(range).unwrap();
+        }
+    }
+    // files found in directories are told apart by their headers
+    if options.recursive && matches.occurrences_of("style") == 0 {
+        options.decorations.header = true;
+    }
+    for component in matches.values_of("style").into_iter().flatten() {
+        match component {
+            "header" => options.decorations.header = true,
+            "numbers" => options.decorations.numbers = true,
+            "grid" => options.decorations.grid = true,
+            "changes" => options.decorations.changes = true,
+            _ => options.decorations = Decorations::default(),
+        }
+    }
+    if let Some(n) = matches.value_of("head") {
+        options.line_ranges.add_head(n.parse().unwrap());
+    }
+    if let Some(n) = matches.value_of("tail") {
+        options.line_ranges.add_tail(n.parse().unwrap());
+    }
+
     options.display_number |= matches.occurrences_of("number") > 0;
     options.display_number |= matches.occurrences_of("number-nonblank") > 0;
-    options.raw_control_chars |= atty::is(atty::Stream::Stdout);
 
     if !options.print_supported {
         if let Some(theme_name) = matches.value_of("theme") {
-            match supported.find_theme(&theme_name) {
+            match supported.find_theme(theme_name) {
                 Ok(th) => options.theme = th,
                 Err(e) => {
                     print_error(&format!("{}: {}", e, theme_name));
@@ -163,32 +656,196 @@ fn parse_arguments(supported: &Supported) -> Arguments {
                 }
             }
         }
+        let find_language = |lang_name: &str| match supported.find_language(lang_name) {
+            Ok(ln) => ln,
+            Err(e) => {
+                print_error(&format!("{}: {}", e, lang_name));
+                supported.print_languages();
+                std::process::exit(1);
+            }
+        };
+        for (ext, lang_name) in &config.extensions {
+            options
+                .syntax_mapping
+                .add_extension(ext, find_language(lang_name));
+        }
+        for mapping in values(&matches, "map-syntax") {
+            let (glob, lang_name) = lang::split_mapping(&mapping).unwrap();
+            options.syntax_mapping.add(glob, find_language(lang_name));
+        }
+        if let Some(lang_name) = matches.value_of("language") {
+            options.language = Some(find_language(lang_name));
+        }
     }
 
     let file_names = matches
+        .command_line()
         .values_of("file")
         .map(|values| values.map(|v| v.to_owned()).collect::<Vec<_>>())
-        .unwrap_or_else(|| vec!["-".to_owned()]);
+        .unwrap_or_else(|| {
+            // files compared by --diff take the place of stdin
+            if options.compare.is_some() {
+                Vec::new()
+            } else {
+                vec!["-".to_owned()]
+            }
+        });
     Arguments {
         options,
         file_names,
     }
 }
 
+/// Tells whether the file at a path is another one than `file`, as after a
+/// rotation.
+#[cfg(unix)]
+fn is_replaced(file: &File, metadata: &fs::Metadata) -> io::Result<bool> {
+    let current = file.metadata()?;
+    Ok(metadata.dev() != current.dev() || metadata.ino() != current.ino())
+}
+
+/// Other systems notice only truncation, by the length of the file.
+#[cfg(not(unix))]
+fn is_replaced(_file: &File, _metadata: &fs::Metadata) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Returns the length up to the end of the last complete line.
+fn complete_len(buf: &[u8]) -> usize {
+    buf.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1)
+}
+
+fn values(matches: &Matches, name: &str) -> Vec<String> {
+    matches
+        .values_of(name)
+        .map(|values| values.map(String::from).collect())
+        .unwrap_or_default()
+}
+
+/// Returns the old and new files given to the option `name`, which is read
+/// from the command line only.
+fn compared(matches: &Matches, name: &str, compare: Compare) -> Option<(Compare, String, String)> {
+    matches.command_line().values_of(name).map(|mut values| {
+        let old = values.next().unwrap().to_owned();
+        (compare, old, values.next().unwrap().to_owned())
+    })
+}
+
 #[derive(Clone)]
-struct Options {
-    display_number: bool,
-    number_nonblack: bool,
-    squeeze_blank: bool,
-    raw_control_chars: bool,
-    print_supported: bool,
-    theme: _generated::Theme,
+pub struct Options {
+    pub display_number: bool,
+    pub number_nonblank: bool,
+    pub squeeze_blank: bool,
+    pub show_ends: bool,
+    pub show_tabs: bool,
+    pub show_nonprinting: bool,
+    pub colored: bool,
+    pub print_supported: bool,
+    pub list_languages: bool,
+    pub language: Option<&'static str>,
+    pub file_name: Option<String>,
+    pub guess: bool,
+    pub encoding: Option<Encoding>,
+    pub binary: Binary,
+    pub line_ranges: LineRanges,
+    pub highlight_lines: LineRanges,
+    pub decorations: Decorations,
+    pub follow: bool,
+    /// How to compare the old and new files of `--diff` or `--side-by-side`.
+    pub compare: Option<(Compare, String, String)>,
+    pub recursive: bool,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub tabs: usize,
+    pub wrap: Wrap,
+    pub paging: Paging,
+    pub syntax_mapping: lang::SyntaxMapping,
+    pub debug: bool,
+    pub theme: _generated::Theme,
+}
+
+/// Decides whether to colorize the output for the value of `--color`.
+///
+/// In the auto mode, a non-empty `NO_COLOR` turns colors off and a
+/// `CLICOLOR_FORCE` other than `0` turns them on even when stdout is not a
+/// terminal. Environment variables are looked up with `var`.
+fn use_color<F>(when: &str, var: F, is_tty: bool) -> bool
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    match when {
+        "always" => true,
+        "never" => false,
+        _ => {
+            let env = |name| var(name).filter(|v| !v.is_empty());
+            if env("NO_COLOR").is_some() {
+                false
+            } else if env("CLICOLOR_FORCE").is_some_and(|v| v != "0") {
+                true
+            } else {
+                is_tty
+            }
+        }
+    }
+}
+
+/// How binary inputs are written.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Binary {
+    Print,
+    Skip,
+    Notice,
+}
+
+/// Parts of the layout around inputs, which are picked by `--style`.
+#[derive(Clone, Copy, Default)]
+pub struct Decorations {
+    pub header: bool,
+    pub numbers: bool,
+    pub changes: bool,
+    pub grid: bool,
+}
+
+impl Decorations {
+    pub fn is_plain(&self) -> bool {
+        !(self.header || self.numbers || self.changes || self.grid)
+    }
+}
+
+/// How long lines are wrapped at the width of the terminal. In the auto mode,
+/// lines are wrapped on a terminal only, and between tokens when they fit in
+/// a row.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Wrap {
+    Auto,
+    Never,
+    Character,
+}
+
+/// How two files are compared, as a unified diff or side by side.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Compare {
+    Unified,
+    SideBySide,
+}
+
+/// When to page the output.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Paging {
+    Auto,
+    Always,
+    Never,
 }
 
 fn print_error(err: &str) {
     let exe = get_exe_name();
     let mut stderr = std::io::stderr();
-    writeln!(&mut stderr, "{}: {}", exe, err).unwrap();
+    // there is nowhere left to report a failure to write to stderr
+    let _ = writeln!(&mut stderr, "{}: {}", exe, err);
+}
+
+fn print_debug(msg: &str) {
+    print_error(&format!("debug: {}", msg))
 }
 
 fn get_exe_name() -> String {
@@ -222,10 +879,106 @@ impl Supported {
             .ok_or(Error::UnsupportedTheme)
     }
 
+    pub fn find_language(&self, name: &str) -> Result<&'static str> {
+        lang::find(name).ok_or(Error::UnsupportedLanguage)
+    }
+
     pub fn print(&self) {
+        println!("Supported Languages");
+        for lang in _generated::langs().iter() {
+            println!(" * {}", lang.name);
+        }
+        println!();
         println!("Supported Themes");
         for &(ref name, _) in &self.themes {
             println!(" * {}", name);
         }
     }
+
+    pub fn print_languages(&self) {
+        let width = _generated::langs()
+            .iter()
+            .map(|lang| lang.name.len())
+            .max()
+            .unwrap_or(0);
+        for lang in _generated::langs().iter() {
+            println!(
+                "{:width$}  {}",
+                lang.name,
+                lang.file_types.join(", "),
+                width = width
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use writer;
+
+    fn civet(options: Options) -> Civet {
+        Civet {
+            ll: lang::LangLoader::new(),
+            args: Arguments {
+                options,
+                file_names: vec!["-".to_owned()],
+            },
+            supported: Supported::new(),
+            failed: Cell::new(false),
+        }
+    }
+
+    fn stdin(civet: &Civet, input: &[u8]) -> String {
+        let mut w = Writer::new(Vec::new(), &civet.args.options);
+        civet.write_stdin(input, &mut w).unwrap();
+        String::from_utf8(w.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn stdin_language() {
+        let input = b"fn main() {}\n";
+        let mut options = writer::tests::options();
+        options.colored = true;
+        // nothing tells the language of plain stdin
+        assert_eq!(stdin(&civet(options.clone()), input), "fn main() {}\n");
+
+        let mut with_language = options.clone();
+        with_language.language = Some("source.rust");
+        let mut with_name = options;
+        with_name.file_name = Some("main.rs".to_owned());
+        for options in &[with_language, with_name] {
+            let out = stdin(&civet(options.clone()), input);
+            assert!(out.starts_with("\x1B[38;5;"), "{:?}", out);
+            assert_eq!(writer::tests::strip_escapes(&out), "fn main() {}\n");
+        }
+    }
+
+    #[test]
+    fn color_modes() {
+        let color = |when, vars: &[(&str, &str)], is_tty| {
+            let var = |name: &str| {
+                vars.iter().find(|v| v.0 == name).map(|v| OsString::from(v.1))
+            };
+            use_color(when, var, is_tty)
+        };
+        let no_color = [("NO_COLOR", "1")];
+        let force = [("CLICOLOR_FORCE", "1")];
+        let both = [("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")];
+        for &is_tty in &[false, true] {
+            for vars in &[&[][..], &no_color, &force, &both] {
+                assert!(color("always", vars, is_tty));
+                assert!(!color("never", vars, is_tty));
+            }
+            assert_eq!(color("auto", &[], is_tty), is_tty);
+            assert!(!color("auto", &no_color, is_tty));
+            assert!(color("auto", &force, is_tty));
+            // NO_COLOR wins over CLICOLOR_FORCE
+            assert!(!color("auto", &both, is_tty));
+            // empty variables are unset, and CLICOLOR_FORCE=0 forces nothing
+            assert_eq!(color("auto", &[("NO_COLOR", "")], is_tty), is_tty);
+            assert_eq!(color("auto", &[("CLICOLOR_FORCE", "")], is_tty), is_tty);
+            assert_eq!(color("auto", &[("CLICOLOR_FORCE", "0")], is_tty), is_tty);
+        }
+    }
 }
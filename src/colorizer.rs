@@ -16,20 +16,15 @@ impl LineColorizer {
         }
     }
 
-    pub fn process_line(&mut self, line: &str) -> String {
+    pub fn process_line<'a>(&mut self, line: &'a str) -> Vec<(Style, &'a str)> {
         let tokens = self.tokenizer.tokenize_line(line);
-        let colored_tokens: Vec<_> = tokens
+        tokens
             .into_iter()
-            .map(|t| {
-                let style = self.scopes.style(&t.scopes);
-                format!(
-                    "{}{}{}",
-                    style.color(),
-                    &line[t.start..t.end],
-                    Style::reset()
-                )
-            })
-            .collect();
-        colored_tokens.join("")
+            .map(|t| (self.scopes.style(&t.scopes), &line[t.start..t.end]))
+            .collect()
+    }
+
+    pub fn invisibles(&self) -> Style {
+        self.scopes.invisibles()
     }
 }
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use clap::{ArgMatches, Values};
+use serde_json;
+
+use app;
+use error::Error;
+
+/// Settings of the config file, which are defaults for the arguments of the
+/// same names.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub theme: Option<String>,
+    pub number: bool,
+    pub paging: Option<String>,
+    pub wrap: Option<String>,
+    pub style: Vec<String>,
+    /// Values of `--map-syntax`, like `"*.conf:toml"`.
+    pub map_syntax: Vec<String>,
+    /// Languages by extensions, like `"rsx": "rust"`.
+    pub extensions: HashMap<String, String>,
+}
+
+impl Config {
+    /// Reads the config file at `path`. A missing file has no settings.
+    pub fn load(path: &Path) -> Result<Config, Error> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    /// Returns the settings as arguments of the command line.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut push = |name: &str, value: &Option<String>| {
+            if let Some(ref value) = *value {
+                args.push(format!("--{}={}", name, value));
+            }
+        };
+        push("theme", &self.theme);
+        push("paging", &self.paging);
+        push("wrap", &self.wrap);
+        if !self.style.is_empty() {
+            args.push(format!("--style={}", self.style.join(",")));
+        }
+        if self.number {
+            args.push("--number".to_owned());
+        }
+        for mapping in &self.map_syntax {
+            args.push(format!("--map-syntax={}", mapping));
+        }
+        args
+    }
+}
+
+/// Returns the path of the config file, which is
+/// `$XDG_CONFIG_HOME/civetcat/config.json` or
+/// `~/.config/civetcat/config.json`.
+pub fn path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|dir| dir.join("civetcat").join("config.json"))
+}
+
+/// Returns the arguments in `CIVET_OPTS`, separated by whitespace and quoted
+/// like in a shell.
+pub fn env_args() -> Result<Vec<String>, String> {
+    env::var("CIVET_OPTS")
+        .map(|opts| split_args(&opts))
+        .unwrap_or_else(|_| Ok(Vec::new()))
+}
+
+/// Splits a line into arguments at whitespace outside of quotes. Single quotes
+/// keep everything in them, and a backslash escapes the next character outside
+/// of quotes and `"` or `\` in double quotes.
+fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    // the argument being read, if any
+    let mut arg: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            _ if c.is_whitespace() => args.extend(arg.take()),
+            '\'' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("unterminated single quote".to_owned()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err("unterminated double quote".to_owned()),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("unterminated double quote".to_owned()),
+                    }
+                }
+            }
+            '\\' => {
+                let arg = arg.get_or_insert_with(String::new);
+                arg.extend(chars.next());
+            }
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+/// Arguments of the command line over those of `CIVET_OPTS` and the config
+/// file. An argument given in a layer replaces all of its values in the layers
+/// below, and input files are read only from the command line.
+pub struct Matches<'a> {
+    layers: Vec<ArgMatches<'a>>,
+}
+
+impl<'a> Matches<'a> {
+    pub fn new(matches: ArgMatches<'a>) -> Matches<'a> {
+        Matches {
+            layers: vec![matches],
+        }
+    }
+
+    /// Adds a layer of default arguments below the others, returning the
+    /// message of clap when they are invalid.
+    pub fn add_defaults(&mut self, args: &[String]) -> Result<(), String> {
+        if args.is_empty() {
+            return Ok(());
+        }
+        let argv = Some("cv".to_owned()).into_iter().chain(args.iter().cloned());
+        let matches = app::initialize()
+            .get_matches_from_safe(argv)
+            .map_err(|e| {
+                let msg = e.message.lines().next().unwrap_or("").to_owned();
+                msg.trim_start_matches("error: ").to_owned()
+            })?;
+        self.layers.push(matches);
+        Ok(())
+    }
+
+    pub fn occurrences_of(&self, name: &str) -> u64 {
+        self.layer(name).occurrences_of(name)
+    }
+
+    pub fn value_of(&self, name: &str) -> Option<&str> {
+        self.layer(name).value_of(name)
+    }
+
+    pub fn values_of<'b>(&'b self, name: &str) -> Option<Values<'b>> {
+        self.layer(name).values_of(name)
+    }
+
+    /// Returns the arguments of the command line alone.
+    pub fn command_line(&self) -> &ArgMatches<'a> {
+        &self.layers[0]
+    }
+
+    // the default values of arguments are taken from the command line
+    fn layer(&self, name: &str) -> &ArgMatches<'a> {
+        self.layers
+            .iter()
+            .find(|m| m.occurrences_of(name) > 0)
+            .unwrap_or(&self.layers[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process};
+
+    #[test]
+    fn quoted_args() {
+        let split = |line| split_args(line).unwrap();
+        assert_eq!(split("  --tabs=4   -n "), ["--tabs=4", "-n"]);
+        assert_eq!(
+            split(r#"--map-syntax "*.my conf:toml" --file-name='a b'"#),
+            ["--map-syntax", "*.my conf:toml", "--file-name=a b"]
+        );
+        assert_eq!(split(r#"a\ b "c\"d" '' 'e\'"#), ["a b", "c\"d", "", "e\\"]);
+        assert!(split_args("'open").is_err());
+        assert!(split_args("\"open").is_err());
+    }
+
+    #[test]
+    fn layers() {
+        let cli = app::initialize().get_matches_from(vec!["cv", "--theme=Monokai", "a.rs"]);
+        let mut matches = Matches::new(cli);
+        let env = split_args("--theme=KimbieDark --tabs=4").unwrap();
+        matches.add_defaults(&env).unwrap();
+        let config = Config {
+            theme: Some("SolarizedDark".to_owned()),
+            wrap: Some("never".to_owned()),
+            ..Config::default()
+        };
+        matches.add_defaults(&config.args()).unwrap();
+
+        assert_eq!(matches.value_of("theme"), Some("Monokai"));
+        assert_eq!(matches.value_of("tabs"), Some("4"));
+        assert_eq!(matches.value_of("wrap"), Some("never"));
+        // defaults of clap come from the command line
+        assert_eq!(matches.value_of("color"), Some("auto"));
+        assert!(matches.add_defaults(&["--no-such-option".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn config_file() {
+        let path = env::temp_dir().join(format!("civet-config-{}.json", process::id()));
+        fs::write(&path, r#"{"theme": "Monokai", "number": true}"#).unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.theme, Some("Monokai".to_owned()));
+        assert!(config.number);
+
+        fs::write(&path, r#"{"colour": "always"}"#).unwrap();
+        match Config::load(&path) {
+            Err(Error::Config(msg)) => assert!(msg.contains("unknown field"), "{}", msg),
+            _ => panic!("unknown field accepted"),
+        }
+        fs::remove_file(&path).unwrap();
+        assert!(Config::load(&path).unwrap().theme.is_none());
+    }
+}
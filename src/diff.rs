@@ -0,0 +1,367 @@
+use std::cmp;
+
+/// What happens to a line, or a word, from the old input to the new one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+/// A step of a diff, with the indices of the lines at which it takes place in
+/// both inputs. A removed line is at `old`, an added one is at `new`, and the
+/// other index is where the line would be in the other input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edit {
+    pub change: Change,
+    pub old: usize,
+    pub new: usize,
+}
+
+/// Finds the shortest edit script from `a` to `b` by the algorithm of Myers
+/// in linear space. Removed items come before added ones in each change.
+pub fn diff<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    diff_range(a, b, 0, 0, &mut edits);
+
+    // changes are found in pieces, which are put in order here
+    let mut i = 0;
+    while i < edits.len() {
+        if edits[i].change == Change::Same {
+            i += 1;
+            continue;
+        }
+        let end = edits[i..]
+            .iter()
+            .position(|e| e.change == Change::Same)
+            .map_or(edits.len(), |n| i + n);
+        let (old, new) = (edits[i].old, edits[i].new);
+        edits[i..end].sort_by_key(|e| e.change == Change::Added);
+        let removed = edits[i..end]
+            .iter()
+            .filter(|e| e.change == Change::Removed)
+            .count();
+        for (n, edit) in edits[i..end].iter_mut().enumerate() {
+            if edit.change == Change::Removed {
+                edit.old = old + n;
+                edit.new = new;
+            } else {
+                edit.old = old + removed;
+                edit.new = new + n - removed;
+            }
+        }
+        i = end;
+    }
+    edits
+}
+
+fn diff_range<T: PartialEq>(a: &[T], b: &[T], old: usize, new: usize, edits: &mut Vec<Edit>) {
+    let prefix = a.iter().zip(b).take_while(|&(x, y)| x == y).count();
+    for i in 0..prefix {
+        edits.push(edit(Change::Same, old + i, new + i));
+    }
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let (old, new) = (old + prefix, new + prefix);
+    let suffix = a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|&(x, y)| x == y)
+        .count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+
+    if a.is_empty() {
+        for i in 0..b.len() {
+            edits.push(edit(Change::Added, old, new + i));
+        }
+    } else if b.is_empty() {
+        for i in 0..a.len() {
+            edits.push(edit(Change::Removed, old + i, new));
+        }
+    } else {
+        let (x, y) = middle(a, b);
+        diff_range(&a[..x], &b[..y], old, new, edits);
+        diff_range(&a[x..], &b[y..], old + x, new + y, edits);
+    }
+
+    for i in 0..suffix {
+        edits.push(edit(Change::Same, old + a.len() + i, new + b.len() + i));
+    }
+}
+
+fn edit(change: Change, old: usize, new: usize) -> Edit {
+    Edit { change, old, new }
+}
+
+/// Finds a point on a shortest edit path by searching from both ends until
+/// the paths meet. `a` and `b` differ at both ends.
+fn middle<T: PartialEq>(a: &[T], b: &[T]) -> (usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m + 1) / 2;
+    let offset = max;
+    let len = 2 * max + 2;
+    // the furthest x reached on each diagonal from the start and from the end
+    let mut forward = vec![-1isize; len as usize];
+    let mut backward = vec![-1isize; len as usize];
+    forward[(offset + 1) as usize] = 0;
+    backward[(offset + 1) as usize] = 0;
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+
+    for d in 0..max {
+        let mut k = -d;
+        while k <= d {
+            let i = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && forward[i - 1] < forward[i + 1]) {
+                forward[i + 1]
+            } else {
+                forward[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[i] = x;
+            if odd && x <= n && y <= m {
+                // the diagonal of the backward search which meets this one
+                let j = offset + delta - k;
+                if j >= 0 && j < len && backward[j as usize] != -1
+                    && x >= n - backward[j as usize]
+                {
+                    return (x as usize, y as usize);
+                }
+            }
+            k += 2;
+        }
+
+        let mut k = -d;
+        while k <= d {
+            let i = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && backward[i - 1] < backward[i + 1]) {
+                backward[i + 1]
+            } else {
+                backward[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[i] = x;
+            if !odd && x <= n && y <= m {
+                let j = offset + delta - k;
+                if j >= 0 && j < len && forward[j as usize] != -1 {
+                    let fx = forward[j as usize];
+                    let fy = fx - (j - offset);
+                    if fx <= n && fy <= m && fx >= n - x {
+                        return (fx as usize, fy as usize);
+                    }
+                }
+            }
+            k += 2;
+        }
+    }
+    (n as usize, 0)
+}
+
+/// A group of edits which is printed under a hunk header, with the lines of
+/// context around the changes.
+pub struct Hunk<'a> {
+    pub edits: &'a [Edit],
+}
+
+impl<'a> Hunk<'a> {
+    /// Returns the ranges of the hunk header like `-12,3 +12,4` as GNU diff
+    /// writes them. The length of a single line is left out, and an empty
+    /// range starts at the line before it.
+    pub fn header(&self) -> String {
+        let first = self.edits[0];
+        let range = |index: usize, len: usize| match len {
+            0 => format!("{},0", index),
+            1 => format!("{}", index + 1),
+            _ => format!("{},{}", index + 1, len),
+        };
+        let old_len = self.edits
+            .iter()
+            .filter(|e| e.change != Change::Added)
+            .count();
+        let new_len = self.edits
+            .iter()
+            .filter(|e| e.change != Change::Removed)
+            .count();
+        format!("-{} +{}", range(first.old, old_len), range(first.new, new_len))
+    }
+}
+
+/// Groups the changes of `edits` into hunks with `context` lines around them.
+/// Changes closer than twice the context go into the same hunk.
+pub fn hunks(edits: &[Edit], context: usize) -> Vec<Hunk<'_>> {
+    let mut hunks = Vec::new();
+    let mut i = 0;
+    while let Some(n) = edits[i..].iter().position(|e| e.change != Change::Same) {
+        let start = (i + n).saturating_sub(context);
+        let mut end = i + n;
+        loop {
+            // the end of the change
+            while end < edits.len() && edits[end].change != Change::Same {
+                end += 1;
+            }
+            let same = edits[end..]
+                .iter()
+                .take_while(|e| e.change == Change::Same)
+                .count();
+            if end + same < edits.len() && same <= 2 * context {
+                end += same;
+            } else {
+                end = cmp::min(end + context, edits.len());
+                break;
+            }
+        }
+        hunks.push(Hunk {
+            edits: &edits[start..end],
+        });
+        i = end;
+    }
+    hunks
+}
+
+/// Byte ranges of a line.
+pub type Ranges = Vec<(usize, usize)>;
+
+/// Least share of the words of a changed line which have to stay the same
+/// for the changed words to be emphasized.
+const MIN_SAME_WORDS: f64 = 0.5;
+
+/// Finds the words which changed between two versions of a line, as byte
+/// ranges of each version. Lines which have too little in common are changed
+/// as a whole, and give no words.
+pub fn changed_words(old: &str, new: &str) -> Option<(Ranges, Ranges)> {
+    let old_words = words(old);
+    let new_words = words(new);
+    let old_texts: Vec<&str> = old_words.iter().map(|&(s, e)| &old[s..e]).collect();
+    let new_texts: Vec<&str> = new_words.iter().map(|&(s, e)| &new[s..e]).collect();
+    let edits = diff(&old_texts, &new_texts);
+
+    let is_word = |s: &str| !s.trim().is_empty();
+    let same = edits
+        .iter()
+        .filter(|e| e.change == Change::Same && is_word(old_texts[e.old]))
+        .count();
+    let total = cmp::max(
+        old_texts.iter().filter(|w| is_word(w)).count(),
+        new_texts.iter().filter(|w| is_word(w)).count(),
+    );
+    if total == 0 || (same as f64) < MIN_SAME_WORDS * total as f64 {
+        return None;
+    }
+
+    let mut old_ranges = Ranges::new();
+    let mut new_ranges = Ranges::new();
+    for edit in &edits {
+        let (ranges, range) = match edit.change {
+            Change::Same => continue,
+            Change::Removed => (&mut old_ranges, old_words[edit.old]),
+            Change::Added => (&mut new_ranges, new_words[edit.new]),
+        };
+        // adjacent words are emphasized together
+        match ranges.last_mut() {
+            Some(last) if last.1 == range.0 => last.1 = range.1,
+            _ => ranges.push(range),
+        }
+    }
+    Some((old_ranges, new_ranges))
+}
+
+/// Splits a line into runs of word characters, runs of whitespace and single
+/// other characters, as byte ranges.
+fn words(line: &str) -> Vec<(usize, usize)> {
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            1
+        } else if c.is_whitespace() {
+            2
+        } else {
+            0
+        }
+    };
+    let mut words: Vec<(usize, usize)> = Vec::new();
+    let mut prev = None;
+    for (i, c) in line.char_indices() {
+        let end = i + c.len_utf8();
+        let class = class(c);
+        match words.last_mut() {
+            Some(last) if class != 0 && prev == Some(class) => last.1 = end,
+            _ => words.push((i, end)),
+        }
+        prev = Some(class);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(s: &str) -> Vec<&str> {
+        s.split_terminator('\n').collect()
+    }
+
+    fn unified(a: &str, b: &str) -> String {
+        let (a, b) = (lines(a), lines(b));
+        let mut out = String::new();
+        let edits = diff(&a, &b);
+        for hunk in hunks(&edits, 1) {
+            out.push_str(&format!("@@ {} @@\n", hunk.header()));
+            for edit in hunk.edits {
+                let line = match edit.change {
+                    Change::Same => format!(" {}", a[edit.old]),
+                    Change::Removed => format!("-{}", a[edit.old]),
+                    Change::Added => format!("+{}", b[edit.new]),
+                };
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn shortest_edits() {
+        let count = |a: &str, b: &str| {
+            let a: Vec<char> = a.chars().collect();
+            let b: Vec<char> = b.chars().collect();
+            diff(&a, &b)
+                .iter()
+                .filter(|e| e.change != Change::Same)
+                .count()
+        };
+        assert_eq!(count("abcabba", "cbabac"), 5);
+        assert_eq!(count("", "abc"), 3);
+        assert_eq!(count("abc", "abc"), 0);
+        assert_eq!(count("ab", "ba"), 2);
+        assert_eq!(count("kitten", "sitting"), 5);
+    }
+
+    #[test]
+    fn unified_hunks() {
+        let a = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        let b = "1\n2\nthree\n4\n5\n6\n7\n8\nnine\n";
+        assert_eq!(
+            unified(a, b),
+            "@@ -2,3 +2,3 @@\n 2\n-3\n+three\n 4\n@@ -8 +8,2 @@\n 8\n+nine\n"
+        );
+        assert_eq!(unified("a\nb\n", "b\n"), "@@ -1,2 +1 @@\n-a\n b\n");
+        assert_eq!(unified("a\n", ""), "@@ -1 +0,0 @@\n-a\n");
+        assert_eq!(unified("", "a\n"), "@@ -0,0 +1 @@\n+a\n");
+        assert_eq!(unified("a\nb\n", "a\nb\n"), "");
+    }
+
+    #[test]
+    fn words_changed() {
+        let changed = changed_words("let x = foo(1);", "let x = bar(1);").unwrap();
+        assert_eq!(changed, (vec![(8, 11)], vec![(8, 11)]));
+        let changed = changed_words("a b c", "a b c d").unwrap();
+        assert_eq!(changed, (vec![], vec![(5, 7)]));
+        assert_eq!(changed_words("one two", "three four"), None);
+    }
+}
@@ -0,0 +1,733 @@
+use std::io::{self, Write};
+use std::{cmp, result};
+
+use atty;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use civet::{Options, Wrap};
+use colorizer::LineColorizer;
+use diff::{self, Change, Edit, Hunk, Ranges};
+use error::Error;
+use style::{Style, StyleTree};
+use term;
+use theme;
+use writer::{CONTINUATION_MARKER, TAB_WIDTH};
+
+type Result<T> = result::Result<T, Error>;
+
+/// Lines of context around the changes of a unified diff.
+const CONTEXT: usize = 3;
+
+/// Width of the marker of a change between the columns of `--side-by-side`,
+/// with the spaces around it.
+const SEPARATOR_WIDTH: usize = 3;
+
+/// Narrowest text of a column of `--side-by-side`.
+const MIN_TEXT_WIDTH: usize = 8;
+
+/// Marks the end of a line cut off at the edge of a column.
+const CUT_MARKER: &str = "…";
+
+/// An input compared by `--diff`, split into lines with their newlines.
+pub struct Side {
+    name: String,
+    lines: Vec<String>,
+    // the styles and lengths of the tokens of each line
+    tokens: Option<Vec<Vec<(Style, usize)>>>,
+    // the bytes of a binary input, which has no lines
+    binary: Option<Vec<u8>>,
+}
+
+impl Side {
+    /// Splits `text` into lines, which are colorized with `lc` from the first
+    /// to the last, so the tokenizer of each side keeps its state across the
+    /// lines left out between hunks.
+    pub fn new(name: &str, text: &str, lc: Option<LineColorizer>) -> Side {
+        let lines: Vec<String> = text.split_inclusive('\n').map(String::from).collect();
+        let tokens = lc.map(|mut lc| {
+            lines
+                .iter()
+                .map(|line| {
+                    lc.process_line(line)
+                        .into_iter()
+                        .map(|(style, token)| (style, token.len()))
+                        .collect()
+                })
+                .collect()
+        });
+        Side {
+            name: name.to_owned(),
+            lines,
+            tokens,
+            binary: None,
+        }
+    }
+
+    /// Keeps a binary input as it is, to tell only whether it differs.
+    pub fn binary(name: &str, bytes: &[u8]) -> Side {
+        Side {
+            name: name.to_owned(),
+            lines: Vec::new(),
+            tokens: None,
+            binary: Some(bytes.to_vec()),
+        }
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.binary.is_some()
+    }
+}
+
+/// A line of a diff with the byte ranges of its changed words.
+struct Line {
+    change: Change,
+    index: usize,
+    emphasis: Ranges,
+}
+
+/// A character of a line laid out in a column, as its text on the screen with
+/// its style and width.
+type Unit = (Option<Style>, String, usize);
+
+/// Writes the differences between two inputs, highlighting both of them.
+pub struct DiffWriter<W: Write> {
+    inner: W,
+    theme: Option<StyleTree>,
+    width: usize,
+    // whether long lines are wrapped in their columns rather than cut off
+    wrap: bool,
+    tabs: usize,
+}
+
+impl<W: Write> DiffWriter<W> {
+    pub fn new(inner: W, options: &Options) -> DiffWriter<W> {
+        DiffWriter {
+            inner,
+            theme: if options.colored {
+                Some(theme::load(options.theme))
+            } else {
+                None
+            },
+            width: term::width(),
+            wrap: match options.wrap {
+                Wrap::Auto => atty::is(atty::Stream::Stdout),
+                Wrap::Character => true,
+                Wrap::Never => false,
+            },
+            tabs: if options.tabs > 0 {
+                options.tabs
+            } else {
+                TAB_WIDTH
+            },
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Writes a unified diff of two inputs, which is empty when they are the
+    /// same.
+    pub fn write_unified(&mut self, old: &Side, new: &Side) -> Result<()> {
+        if old.is_binary() || new.is_binary() {
+            return self.write_binary(old, new);
+        }
+        let edits = diff::diff(&old.lines, &new.lines);
+        let hunks = diff::hunks(&edits, CONTEXT);
+        if hunks.is_empty() {
+            return Ok(());
+        }
+        self.write_hunks(old, new, &hunks)
+            .and_then(|_| self.inner.flush())
+            .map_err(Error::Output)
+    }
+
+    fn write_hunks(&mut self, old: &Side, new: &Side, hunks: &[Hunk<'_>]) -> io::Result<()> {
+        let header = self.scope_style("meta.diff.header");
+        self.write_meta(&format!("--- {}", old.name), header.as_ref())?;
+        self.write_meta(&format!("+++ {}", new.name), header.as_ref())?;
+        let range = self.scope_style("meta.diff.range");
+
+        for hunk in hunks {
+            self.write_meta(&format!("@@ {} @@", hunk.header()), range.as_ref())?;
+            for (removed, added) in group(hunk.edits, old, new) {
+                // a line which is the same is written once from the new input
+                for line in removed.iter().filter(|l| l.change != Change::Same) {
+                    self.write_line(old, line)?;
+                }
+                for line in &added {
+                    self.write_line(new, line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a line of a unified diff with its marker.
+    fn write_line(&mut self, side: &Side, line: &Line) -> io::Result<()> {
+        let (marker, scopes): (&str, &[&str]) = match line.change {
+            Change::Same => (" ", &[]),
+            Change::Removed => ("-", &["markup.deleted"]),
+            Change::Added => ("+", &["markup.inserted"]),
+        };
+        let (tint, _) = self.backgrounds(line.change);
+        let marker_style = self.theme
+            .as_ref()
+            .map(|theme| paint(theme.style(scopes), &tint));
+        self.write_styled(marker, marker_style.as_ref())?;
+        for (style, text) in self.segments(side, line) {
+            self.write_styled(text, style.as_ref())?;
+        }
+        if let Some(ref tint) = tint {
+            // erasing the rest of the line fills it with the background
+            write!(self.inner, "{}\x1B[K", tint.color())?;
+        }
+        self.end_row()?;
+        self.write_missing_newline(side.lines[line.index].ends_with('\n'))
+    }
+
+    fn write_missing_newline(&mut self, newline: bool) -> io::Result<()> {
+        if newline {
+            return Ok(());
+        }
+        let style = self.scope_style("meta.diff");
+        self.write_meta("\\ No newline at end of file", style.as_ref())
+    }
+
+    /// Writes two inputs in two columns with their line numbers, putting the
+    /// lines of each change side by side. Between the columns, `|` marks
+    /// changed lines and `<` and `>` lines only on the left or the right, as
+    /// `diff -y` does.
+    pub fn write_side_by_side(&mut self, left: &Side, right: &Side) -> Result<()> {
+        if left.is_binary() || right.is_binary() {
+            return self.write_binary(left, right);
+        }
+        let edits = diff::diff(&left.lines, &right.lines);
+        let digits = cmp::max(left.lines.len(), right.lines.len()).to_string().len();
+        let column = self.width.saturating_sub(SEPARATOR_WIDTH) / 2;
+        let text_width = cmp::max(column.saturating_sub(digits + 1), MIN_TEXT_WIDTH);
+        self.write_columns(left, right, &edits, digits, text_width)
+            .and_then(|_| self.inner.flush())
+            .map_err(Error::Output)
+    }
+
+    /// Writes a notice like GNU diff does when either input is binary and
+    /// they differ, rather than their bytes.
+    fn write_binary(&mut self, old: &Side, new: &Side) -> Result<()> {
+        if old.binary == new.binary {
+            return Ok(());
+        }
+        let style = self.scope_style("meta.diff");
+        let notice = format!("Binary files {} and {} differ", old.name, new.name);
+        self.write_meta(&notice, style.as_ref())
+            .and_then(|_| self.inner.flush())
+            .map_err(Error::Output)
+    }
+
+    fn write_columns(
+        &mut self,
+        left: &Side,
+        right: &Side,
+        edits: &[Edit],
+        digits: usize,
+        text_width: usize,
+    ) -> io::Result<()> {
+        let header = self.scope_style("meta.diff.header");
+        let column = digits + 1 + text_width;
+        let (left_name, used) = fit(&left.name, column);
+        let (right_name, _) = fit(&right.name, column);
+        let padding = " ".repeat(column - used + SEPARATOR_WIDTH);
+        self.write_meta(&format!("{}{}{}", left_name, padding, right_name), header.as_ref())?;
+
+        for (removed, added) in group(edits, left, right) {
+            for k in 0..cmp::max(removed.len(), added.len()) {
+                let (l, r) = (removed.get(k), added.get(k));
+                let (marker, scopes): (&str, &[&str]) = match (l, r) {
+                    (Some(l), Some(_)) if l.change == Change::Same => (" ", &[]),
+                    (Some(_), Some(_)) => ("|", &["markup.changed"]),
+                    (Some(_), None) => ("<", &["markup.deleted"]),
+                    _ => (">", &["markup.inserted"]),
+                };
+                let marker_style = self.theme.as_ref().map(|theme| theme.style(scopes));
+                let left_rows = l.map_or_else(Vec::new, |l| self.layout(left, l, text_width));
+                let right_rows = r.map_or_else(Vec::new, |r| self.layout(right, r, text_width));
+
+                for row in 0..cmp::max(left_rows.len(), right_rows.len()) {
+                    self.write_cell(l, left_rows.get(row), row, digits, text_width, true)?;
+                    let marker = if row == 0 { marker } else { " " };
+                    self.write_styled(&format!(" {} ", marker), marker_style.as_ref())?;
+                    self.write_cell(r, right_rows.get(row), row, digits, text_width, false)?;
+                    self.end_row()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a row of a line in a column with the line number in the gutter,
+    /// or the continuation marker in the rows it wraps into. A column without
+    /// the line is left blank. The row is padded to `width` columns when
+    /// `pad` is true or the line is changed, to fill it with the background.
+    fn write_cell(
+        &mut self,
+        line: Option<&Line>,
+        units: Option<&Vec<Unit>>,
+        row: usize,
+        digits: usize,
+        width: usize,
+        pad: bool,
+    ) -> io::Result<()> {
+        let gutter = match (line, units) {
+            (Some(line), _) if row == 0 => format!("{:>1$} ", line.index + 1, digits),
+            (Some(_), Some(_)) => format!("{:>1$} ", CONTINUATION_MARKER, digits),
+            _ => format!("{:1$}", "", digits + 1),
+        };
+        let gutter_style = self.theme.as_ref().map(|t| t.gutter());
+        self.write_styled(&gutter, gutter_style.as_ref())?;
+
+        let mut used = 0;
+        let mut color = None;
+        for &(ref style, ref text, unit_width) in units.into_iter().flatten() {
+            // the color is written once for a run of characters in a style
+            let unit_color = style.as_ref().map(|s| s.color());
+            if unit_color != color {
+                self.inner.write_all(unit_color.as_ref().map_or("", |c| c).as_bytes())?;
+                color = unit_color;
+            }
+            self.inner.write_all(text.as_bytes())?;
+            used += unit_width;
+        }
+        let tint = line.and_then(|line| self.backgrounds(line.change).0);
+        if (pad || tint.is_some()) && used < width {
+            let fill = self.theme.as_ref().map(|_| tint.unwrap_or_else(Style::empty));
+            self.write_styled(&" ".repeat(width - used), fill.as_ref())?;
+        }
+        if self.theme.is_some() {
+            self.inner.write_all(Style::reset().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Lays out a line into rows of `width` columns, wrapping it or cutting it
+    /// off at the edge of the column. Tabs are expanded into spaces up to the
+    /// tab stops of their row, and other control characters are written like
+    /// `^A`.
+    fn layout(&self, side: &Side, line: &Line, width: usize) -> Vec<Vec<Unit>> {
+        let mut rows = vec![Vec::new()];
+        let mut used = 0;
+        for (style, text) in self.segments(side, line) {
+            for c in text.chars() {
+                let (text, unit_width) = match c {
+                    '\t' => {
+                        // a tab goes up to the edge of the row, or to the
+                        // first stop of the next row when the row is full
+                        let w = match cmp::min(self.tabs - used % self.tabs, width - used) {
+                            0 => cmp::min(self.tabs, width),
+                            w => w,
+                        };
+                        (" ".repeat(w), w)
+                    }
+                    '\x7F' => ("^?".to_owned(), 2),
+                    c if (c as u32) < 32 => (format!("^{}", (c as u8 + 64) as char), 2),
+                    c => (c.to_string(), c.width().unwrap_or(0)),
+                };
+                if used + unit_width > width && used > 0 {
+                    rows.push(Vec::new());
+                    used = 0;
+                }
+                used += unit_width;
+                rows.last_mut().unwrap().push((style.clone(), text, unit_width));
+            }
+        }
+        if self.wrap || rows.len() == 1 {
+            return rows;
+        }
+
+        // the last column of the first row is left for the cut marker
+        let mut row = rows.remove(0);
+        let mut used: usize = row.iter().map(|unit| unit.2).sum();
+        let mut style = rows[0][0].0.clone();
+        while used >= width {
+            let unit = row.pop().unwrap();
+            used -= unit.2;
+            style = unit.0;
+        }
+        row.push((style, CUT_MARKER.to_owned(), 1));
+        vec![row]
+    }
+
+    /// Returns the text of a line without its newline in pieces with their
+    /// styles, painting the tokens over the background of the change and the
+    /// changed words over a stronger one.
+    fn segments<'s>(&self, side: &'s Side, line: &Line) -> Vec<(Option<Style>, &'s str)> {
+        let text = &side.lines[line.index];
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        let theme = match self.theme {
+            Some(ref theme) => theme,
+            None => return vec![(None, text)],
+        };
+        let (tint, strong) = self.backgrounds(line.change);
+        let whole_line = [(theme.style::<&str>(&[]), text.len())];
+        let tokens = side.tokens
+            .as_ref()
+            .map_or(&whole_line[..], |tokens| &tokens[line.index][..]);
+
+        let mut segments = Vec::new();
+        let mut start = 0;
+        for &(ref style, len) in tokens {
+            let end = cmp::min(start + len, text.len());
+            let mut pos = start;
+            while pos < end {
+                // the part of the token up to where the emphasis begins or ends
+                let (bg, next) = match line.emphasis.iter().find(|&&(_, e)| e > pos) {
+                    Some(&(s, e)) if s <= pos => (&strong, cmp::min(e, end)),
+                    Some(&(s, _)) => (&tint, cmp::min(s, end)),
+                    None => (&tint, end),
+                };
+                segments.push((Some(paint(style.clone(), bg)), &text[pos..next]));
+                pos = next;
+            }
+            start += len;
+        }
+        segments
+    }
+
+    /// Returns the backgrounds of the theme for a changed line and for the
+    /// words changed in it.
+    fn backgrounds(&self, change: Change) -> (Option<Style>, Option<Style>) {
+        let kind = match change {
+            Change::Same => return (None, None),
+            Change::Removed => "removed",
+            Change::Added => "inserted",
+        };
+        match self.theme {
+            Some(ref theme) => (
+                theme.background(&format!("diffEditor.{}LineBackground", kind)),
+                theme.background(&format!("diffEditor.{}TextBackground", kind)),
+            ),
+            None => (None, None),
+        }
+    }
+
+    fn end_row(&mut self) -> io::Result<()> {
+        if self.theme.is_some() {
+            self.inner.write_all(Style::reset().as_bytes())?;
+        }
+        self.inner.write_all(b"\n")
+    }
+
+    fn write_styled(&mut self, text: &str, style: Option<&Style>) -> io::Result<()> {
+        if let Some(style) = style {
+            self.inner.write_all(style.color().as_bytes())?;
+        }
+        self.inner.write_all(text.as_bytes())
+    }
+
+    fn write_meta(&mut self, text: &str, style: Option<&Style>) -> io::Result<()> {
+        match style {
+            Some(style) => writeln!(self.inner, "{}{}{}", style.color(), text, Style::reset()),
+            None => writeln!(self.inner, "{}", text),
+        }
+    }
+
+    fn scope_style(&self, scope: &str) -> Option<Style> {
+        self.theme.as_ref().map(|theme| theme.style(&[scope]))
+    }
+}
+
+/// Groups edits into lines which are the same in both inputs and into
+/// changes, giving the lines of each input. The removed and added lines of a
+/// change are paired in order to find the words changed in them.
+fn group(edits: &[Edit], old: &Side, new: &Side) -> Vec<(Vec<Line>, Vec<Line>)> {
+    let line = |change: Change, index: usize| Line {
+        change,
+        index,
+        emphasis: Ranges::new(),
+    };
+    let mut groups = Vec::new();
+    let mut i = 0;
+    while i < edits.len() {
+        let edit = edits[i];
+        if edit.change == Change::Same {
+            groups.push((vec![line(Change::Same, edit.old)], vec![line(Change::Same, edit.new)]));
+            i += 1;
+            continue;
+        }
+        let end = edits[i..]
+            .iter()
+            .position(|e| e.change == Change::Same)
+            .map_or(edits.len(), |n| i + n);
+        let mut removed: Vec<Line> = edits[i..end]
+            .iter()
+            .filter(|e| e.change == Change::Removed)
+            .map(|e| line(Change::Removed, e.old))
+            .collect();
+        let mut added: Vec<Line> = edits[i..end]
+            .iter()
+            .filter(|e| e.change == Change::Added)
+            .map(|e| line(Change::Added, e.new))
+            .collect();
+        for (o, n) in removed.iter_mut().zip(added.iter_mut()) {
+            let words = diff::changed_words(&old.lines[o.index], &new.lines[n.index]);
+            if let Some((old_words, new_words)) = words {
+                o.emphasis = old_words;
+                n.emphasis = new_words;
+            }
+        }
+        groups.push((removed, added));
+        i = end;
+    }
+    groups
+}
+
+/// Cuts off a name wider than `width` columns with the cut marker, giving it
+/// with the columns it takes.
+fn fit(name: &str, width: usize) -> (String, usize) {
+    if name.width() <= width {
+        return (name.to_owned(), name.width());
+    }
+    let mut fitted = String::new();
+    let mut used = 0;
+    for c in name.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w >= width {
+            break;
+        }
+        fitted.push(c);
+        used += w;
+    }
+    fitted.push_str(CUT_MARKER);
+    (fitted, used + 1)
+}
+
+fn paint(style: Style, bg: &Option<Style>) -> Style {
+    match *bg {
+        Some(ref bg) => style.overlap(bg),
+        None => style,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THEME: &str = r#"{
+        "tokenColors": [
+            {"scope": "keyword", "settings": {"foreground": 1}},
+            {"scope": "markup.deleted", "settings": {"foreground": 2}},
+            {"scope": "markup.inserted", "settings": {"foreground": 3}}
+        ],
+        "colors": {
+            "diffEditor.removedLineBackground": 52,
+            "diffEditor.removedTextBackground": 88,
+            "diffEditor.insertedLineBackground": 22,
+            "diffEditor.insertedTextBackground": 28
+        }
+    }"#;
+
+    fn writer(colored: bool) -> DiffWriter<Vec<u8>> {
+        DiffWriter {
+            inner: Vec::new(),
+            theme: if colored {
+                Some(StyleTree::create(THEME).unwrap())
+            } else {
+                None
+            },
+            width: 40,
+            wrap: false,
+            tabs: 4,
+        }
+    }
+
+    /// Makes a side whose lines start with a keyword of `keyword_len` bytes,
+    /// followed by a plain token up to the end of the line.
+    fn side(name: &str, text: &str, keyword_len: Option<usize>) -> Side {
+        let mut side = Side::new(name, text, None);
+        side.tokens = keyword_len.map(|len| {
+            let theme = StyleTree::create(THEME).unwrap();
+            side.lines
+                .iter()
+                .map(|line| {
+                    vec![
+                        (theme.style(&["keyword"]), len),
+                        (theme.style::<&str>(&[]), line.len() - len),
+                    ]
+                })
+                .collect()
+        });
+        side
+    }
+
+    fn unified(w: &mut DiffWriter<Vec<u8>>, old: &Side, new: &Side) -> String {
+        w.write_unified(old, new).unwrap();
+        String::from_utf8(w.inner.split_off(0)).unwrap()
+    }
+
+    #[test]
+    fn missing_newline() {
+        let mut w = writer(false);
+        let (old, new) = (side("a", "x\ny", None), side("b", "x\nz\n", None));
+        assert_eq!(
+            unified(&mut w, &old, &new),
+            "--- a\n+++ b\n@@ -1,2 +1,2 @@\n x\n-y\n\\ No newline at end of file\n+z\n"
+        );
+        // a last line which is the same is written once with its notice
+        let (old, new) = (side("a", "x\ny", None), side("b", "w\ny", None));
+        assert_eq!(
+            unified(&mut w, &old, &new),
+            "--- a\n+++ b\n@@ -1,2 +1,2 @@\n-x\n+w\n y\n\\ No newline at end of file\n"
+        );
+        assert_eq!(unified(&mut w, &old, &old), "");
+    }
+
+    #[test]
+    fn split_at_emphasis() {
+        let w = writer(true);
+        let old = side("a", "let x = 1;\n", Some(3));
+        let line = Line {
+            change: Change::Removed,
+            index: 0,
+            emphasis: vec![(2, 5), (8, 9)],
+        };
+        let segments: Vec<_> = w.segments(&old, &line)
+            .into_iter()
+            .map(|(style, text)| (style.unwrap().color(), text))
+            .collect();
+        assert_eq!(
+            segments,
+            [
+                ("\x1B[38;5;1;48;5;52m".to_owned(), "le"),
+                ("\x1B[38;5;1;48;5;88m".to_owned(), "t"),
+                ("\x1B[48;5;88m".to_owned(), " x"),
+                ("\x1B[48;5;52m".to_owned(), " = "),
+                ("\x1B[48;5;88m".to_owned(), "1"),
+                ("\x1B[48;5;52m".to_owned(), ";"),
+            ]
+        );
+    }
+
+    #[test]
+    fn layer_styles_over_tint() {
+        let mut w = writer(true);
+        let old = side("a", "let x = 1;\n", Some(3));
+        let new = side("b", "let x = 2;\n", Some(3));
+        let out = unified(&mut w, &old, &new);
+        let lines: Vec<&str> = out.lines().skip(3).collect();
+        // the marker in the color of the change, tokens over the line
+        // background and the changed word over the stronger one
+        assert_eq!(
+            lines,
+            [
+                "\x1B[38;5;2;48;5;52m-\x1B[38;5;1;48;5;52mlet\x1B[48;5;52m x = \
+                 \x1B[48;5;88m1\x1B[48;5;52m;\x1B[48;5;52m\x1B[K\x1B[0m",
+                "\x1B[38;5;3;48;5;22m+\x1B[38;5;1;48;5;22mlet\x1B[48;5;22m x = \
+                 \x1B[48;5;28m2\x1B[48;5;22m;\x1B[48;5;22m\x1B[K\x1B[0m",
+            ]
+        );
+    }
+
+    /// Lays out the first line of `text` as an added line, giving its rows
+    /// as text.
+    fn rows(w: &DiffWriter<Vec<u8>>, text: &str, width: usize) -> Vec<String> {
+        let line = Line {
+            change: Change::Added,
+            index: 0,
+            emphasis: Ranges::new(),
+        };
+        w.layout(&side("a", text, None), &line, width)
+            .iter()
+            .map(|row| row.iter().map(|unit| unit.1.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn cut_off() {
+        let w = writer(false);
+        assert_eq!(rows(&w, "abcdefgh\n", 8), ["abcdefgh"]);
+        assert_eq!(rows(&w, "abcdefghi\n", 8), ["abcdefg…"]);
+        // a wide character which would reach the last column is cut off too
+        assert_eq!(rows(&w, "日本語の文字\n", 8), ["日本語…"]);
+        assert_eq!(rows(&w, "a\x01b\x7Fcdefg\n", 8), ["a^Ab^?c…"]);
+    }
+
+    #[test]
+    fn cut_off_style() {
+        let w = writer(true);
+        let theme = StyleTree::create(THEME).unwrap();
+        let mut new = side("b", "abcdefg日本語の文字\n", None);
+        new.tokens = Some(vec![vec![
+            (theme.style::<&str>(&[]), 7),
+            (theme.style(&["keyword"]), 12),
+            (theme.style(&["markup.deleted"]), 7),
+        ]]);
+        let line = Line {
+            change: Change::Added,
+            index: 0,
+            emphasis: Ranges::new(),
+        };
+        // the marker takes the style of the text it stands for, which wraps
+        // to the second of three rows
+        let row = w.layout(&new, &line, 8).remove(0);
+        let marker = row.last().unwrap();
+        assert_eq!(marker.1, "…");
+        assert_eq!(marker.0.as_ref().unwrap().color(), "\x1B[38;5;1;48;5;22m");
+    }
+
+    #[test]
+    fn wrap_in_column() {
+        let mut w = writer(false);
+        w.wrap = true;
+        assert_eq!(rows(&w, "abcdefghij\n", 8), ["abcdefgh", "ij"]);
+        assert_eq!(rows(&w, "日本語の文字\n", 7), ["日本語", "の文字"]);
+        // tab stops are counted from the start of each row
+        assert_eq!(rows(&w, "abcdefghijk\tl\n", 10), ["abcdefghij", "k   l"]);
+        assert_eq!(rows(&w, "abcdefghij\tk\n", 10), ["abcdefghij", "    k"]);
+        assert_eq!(rows(&w, "abcdefghi\tk\n", 10), ["abcdefghi ", "k"]);
+    }
+
+    #[test]
+    fn side_by_side() {
+        let mut w = writer(false);
+        w.wrap = true;
+        w.width = 29;
+        let old = side("old", &"x\n".repeat(9), None);
+        let new = side("new", &format!("{}a longer line\nz\n", "x\n".repeat(8)), None);
+        w.write_side_by_side(&old, &new).unwrap();
+        let out = String::from_utf8(w.inner.split_off(0)).unwrap();
+        // the gutters are as wide as the numbers of the longer input
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "old             new");
+        assert_eq!(lines[1], " 1 x             1 x");
+        assert_eq!(
+            &lines[9..],
+            [
+                " 9 x          |  9 a longer l",
+                "                 ↪ ine",
+                "              > 10 z",
+            ]
+        );
+    }
+
+    #[test]
+    fn fit_names() {
+        assert_eq!(fit("old", 8), ("old".to_owned(), 3));
+        assert_eq!(fit("古いファイル", 8), ("古いフ…".to_owned(), 7));
+        assert_eq!(fit("a/long/name", 8), ("a/long/…".to_owned(), 8));
+
+        let mut w = writer(false);
+        w.width = 29;
+        let (old, new) = (side("古いファイル.txt", "x\n", None), side("b", "y\n", None));
+        w.write_side_by_side(&old, &new).unwrap();
+        let out = String::from_utf8(w.inner).unwrap();
+        assert_eq!(out.lines().next(), Some("古いファイル…   b"));
+    }
+
+    #[test]
+    fn binary_notice() {
+        let mut w = writer(false);
+        let (old, new) = (Side::binary("a", b"\x00\x01"), Side::binary("b", b"\x00\x02"));
+        assert_eq!(unified(&mut w, &old, &new), "Binary files a and b differ\n");
+        assert_eq!(unified(&mut w, &old, &side("b", "x\n", None)), "Binary files a and b differ\n");
+        assert_eq!(unified(&mut w, &old, &Side::binary("b", b"\x00\x01")), "");
+    }
+}
@@ -0,0 +1,236 @@
+use std::char;
+use std::io::{self, BufRead, Read};
+
+/// Character encodings of inputs, which are decoded into UTF-8.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Latin9,
+    Windows1252,
+}
+
+/// Names accepted by `--encoding`.
+pub static NAMES: &[&str] = &[
+    "utf-8",
+    "utf-16le",
+    "utf-16be",
+    "latin1",
+    "iso-8859-1",
+    "latin9",
+    "iso-8859-15",
+    "cp1252",
+    "windows-1252",
+];
+
+impl Encoding {
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        match name.to_lowercase().as_str() {
+            "utf-8" => Some(Encoding::Utf8),
+            "utf-16le" => Some(Encoding::Utf16Le),
+            "utf-16be" => Some(Encoding::Utf16Be),
+            "latin1" | "iso-8859-1" => Some(Encoding::Latin1),
+            "latin9" | "iso-8859-15" => Some(Encoding::Latin9),
+            "cp1252" | "windows-1252" => Some(Encoding::Windows1252),
+            _ => None,
+        }
+    }
+
+    /// Detects the encoding from a byte order mark, returning the encoding
+    /// and the length of the mark.
+    pub fn from_bom(buf: &[u8]) -> Option<(Encoding, usize)> {
+        if buf.starts_with(b"\xEF\xBB\xBF") {
+            Some((Encoding::Utf8, 3))
+        } else if buf.starts_with(b"\xFF\xFE") {
+            Some((Encoding::Utf16Le, 2))
+        } else if buf.starts_with(b"\xFE\xFF") {
+            Some((Encoding::Utf16Be, 2))
+        } else {
+            None
+        }
+    }
+}
+
+/// A reader which decodes its inner reader into UTF-8. UTF-8 is passed
+/// through as it is, and invalid sequences of UTF-16 are replaced with
+/// U+FFFD.
+pub struct Decoder<R> {
+    inner: R,
+    encoding: Encoding,
+    decoded: Vec<u8>,
+    pos: usize,
+    // a byte or a high surrogate left at the end of the last chunk
+    pending: Vec<u8>,
+}
+
+impl<R: BufRead> Decoder<R> {
+    pub fn new(inner: R, encoding: Encoding) -> Decoder<R> {
+        Decoder {
+            inner,
+            encoding,
+            decoded: Vec::new(),
+            pos: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    fn decode_chunk(&mut self) -> io::Result<()> {
+        self.decoded.clear();
+        self.pos = 0;
+
+        let mut chunk = ::std::mem::take(&mut self.pending);
+        let (len, eof) = {
+            let buf = self.inner.fill_buf()?;
+            chunk.extend_from_slice(buf);
+            (buf.len(), buf.is_empty())
+        };
+        self.inner.consume(len);
+
+        let mut s = String::new();
+        match self.encoding {
+            Encoding::Utf8 => unreachable!(),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let mut units: Vec<u16> = chunk
+                    .chunks(2)
+                    .filter(|pair| pair.len() == 2)
+                    .map(|pair| match self.encoding {
+                        Encoding::Utf16Le => u16::from(pair[0]) | u16::from(pair[1]) << 8,
+                        _ => u16::from(pair[0]) << 8 | u16::from(pair[1]),
+                    })
+                    .collect();
+                let mut rest = chunk.len() % 2;
+                if !eof && units.last().is_some_and(|u| (0xD800..0xDC00).contains(u)) {
+                    units.pop();
+                    rest += 2;
+                }
+                if eof {
+                    rest = 0;
+                }
+                s.extend(
+                    char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)),
+                );
+                if eof && chunk.len() % 2 == 1 {
+                    s.push(char::REPLACEMENT_CHARACTER);
+                }
+                self.pending = chunk[chunk.len() - rest..].to_vec();
+            }
+            Encoding::Latin1 => s.extend(chunk.iter().map(|&b| char::from(b))),
+            Encoding::Latin9 => s.extend(chunk.iter().map(|&b| latin9(b))),
+            Encoding::Windows1252 => s.extend(chunk.iter().map(|&b| windows1252(b))),
+        }
+        self.decoded = s.into_bytes();
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let mut available = self.fill_buf()?;
+            available.read(buf)?
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Decoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.encoding == Encoding::Utf8 {
+            return self.inner.fill_buf();
+        }
+        // a chunk may decode to nothing while a surrogate pair is split
+        while self.pos >= self.decoded.len() {
+            self.decode_chunk()?;
+            if self.decoded.is_empty() && self.pending.is_empty() {
+                break;
+            }
+        }
+        Ok(&self.decoded[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if self.encoding == Encoding::Utf8 {
+            self.inner.consume(amt)
+        } else {
+            self.pos += amt
+        }
+    }
+}
+
+fn latin9(b: u8) -> char {
+    match b {
+        0xA4 => '\u{20AC}',
+        0xA6 => '\u{160}',
+        0xA8 => '\u{161}',
+        0xB4 => '\u{17D}',
+        0xB8 => '\u{17E}',
+        0xBC => '\u{152}',
+        0xBD => '\u{153}',
+        0xBE => '\u{178}',
+        _ => char::from(b),
+    }
+}
+
+static WINDOWS1252: [u16; 32] = [
+    0x20AC, 0x81, 0x201A, 0x192, 0x201E, 0x2026, 0x2020, 0x2021, 0x2C6, 0x2030, 0x160, 0x2039,
+    0x152, 0x8D, 0x17D, 0x8F, 0x90, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x2DC, 0x2122, 0x161, 0x203A, 0x153, 0x9D, 0x17E, 0x178,
+];
+
+fn windows1252(b: u8) -> char {
+    match b {
+        0x80..=0x9F => char::from_u32(u32::from(WINDOWS1252[(b - 0x80) as usize])).unwrap(),
+        _ => char::from(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8], encoding: Encoding) -> String {
+        let mut s = String::new();
+        Decoder::new(bytes, encoding).read_to_string(&mut s).unwrap();
+        s
+    }
+
+    #[test]
+    fn bom() {
+        assert_eq!(Encoding::from_bom(b"\xEF\xBB\xBFfn"), Some((Encoding::Utf8, 3)));
+        assert_eq!(Encoding::from_bom(b"\xFF\xFEf\x00"), Some((Encoding::Utf16Le, 2)));
+        assert_eq!(Encoding::from_bom(b"\xFE\xFF\x00f"), Some((Encoding::Utf16Be, 2)));
+        assert_eq!(Encoding::from_bom(b"fn"), None);
+    }
+
+    #[test]
+    fn utf16() {
+        assert_eq!(decode(b"h\x00i\x00\n\x00", Encoding::Utf16Le), "hi\n");
+        assert_eq!(decode(b"\x00h\x00i", Encoding::Utf16Be), "hi");
+        // a surrogate pair and a lone surrogate
+        assert_eq!(decode(b"\x3D\xD8\x00\xDE", Encoding::Utf16Le), "\u{1F600}");
+        assert_eq!(decode(b"\x3D\xD8a\x00", Encoding::Utf16Le), "\u{FFFD}a");
+        assert_eq!(decode(b"a\x00b", Encoding::Utf16Le), "a\u{FFFD}");
+    }
+
+    #[test]
+    fn utf16_split_chunks() {
+        let bytes = b"\x3D\xD8\x00\xDEa\x00";
+        let reader = io::BufReader::with_capacity(3, &bytes[..]);
+        let mut s = String::new();
+        Decoder::new(reader, Encoding::Utf16Le)
+            .read_to_string(&mut s)
+            .unwrap();
+        assert_eq!(s, "\u{1F600}a");
+    }
+
+    #[test]
+    fn single_byte() {
+        assert_eq!(decode(b"caf\xE9", Encoding::Latin1), "caf\u{E9}");
+        assert_eq!(decode(b"\xA4", Encoding::Latin9), "\u{20AC}");
+        assert_eq!(decode(b"\x93q\x94", Encoding::Windows1252), "\u{201C}q\u{201D}");
+        assert_eq!(decode(b"plain", Encoding::Utf8), "plain");
+    }
+}
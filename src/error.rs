@@ -4,14 +4,30 @@ use std::{fmt, io};
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
+    /// Writing to stdout or the pager failed.
+    Output(io::Error),
+    /// A grammar is missing or is not valid JSON of a grammar.
+    Grammar(String),
+    /// A pattern of a grammar cannot be compiled.
+    Regex { pattern: String, message: String },
+    /// An include refers to a rule missing from the repository.
+    Include(String),
+    /// The config file is not valid JSON of the settings.
+    Config(String),
     UnsupportedTheme,
+    UnsupportedLanguage,
 }
 
 impl error::Error for Error {
     fn description(&self) -> &str {
         match *self {
-            Error::Io(ref e) => e.description(),
+            Error::Io(ref e) | Error::Output(ref e) => e.description(),
+            Error::Grammar(_) => "Invalid Grammar",
+            Error::Regex { .. } => "Invalid Pattern",
+            Error::Include(_) => "Unresolved Include",
+            Error::Config(_) => "Invalid Config",
             Error::UnsupportedTheme => "Unsupported Theme",
+            Error::UnsupportedLanguage => "Unsupported Language",
         }
     }
 }
@@ -20,6 +36,14 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Error::Io(ref e) => e.fmt(f),
+            Error::Output(ref e) => write!(f, "write error: {}", e),
+            Error::Grammar(ref msg) => write!(f, "invalid grammar: {}", msg),
+            Error::Regex {
+                ref pattern,
+                ref message,
+            } => write!(f, "cannot compile pattern {:?}: {}", pattern, message),
+            Error::Include(ref name) => write!(f, "include not found: {}", name),
+            Error::Config(ref msg) => write!(f, "invalid config: {}", msg),
             _ => write!(f, "{}", self.description()),
         }
     }
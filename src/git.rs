@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// A change of a line from the version in the git index.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+    Added,
+    Modified,
+    RemovedAbove,
+    RemovedBelow,
+}
+
+/// Changes by line numbers starting from 1.
+pub type LineChanges = HashMap<usize, Change>;
+
+/// Compares a file in a git working tree with its version in the index by
+/// calling `git diff`. Files out of a working tree have no changes.
+pub fn changes(path: &Path) -> LineChanges {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = match path.file_name() {
+        Some(name) => name,
+        None => return LineChanges::new(),
+    };
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(["diff", "--no-color", "--no-ext-diff", "-U0", "--"])
+        .arg(file_name)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(ref output) if output.status.success() => {
+            parse_diff(&String::from_utf8_lossy(&output.stdout))
+        }
+        _ => LineChanges::new(),
+    }
+}
+
+/// Reads the hunk headers of a unified diff without context lines.
+fn parse_diff(diff: &str) -> LineChanges {
+    let mut changes = LineChanges::new();
+    for header in diff.lines().filter(|l| l.starts_with("@@ ")) {
+        let mut ranges = header.split_whitespace().skip(1);
+        let (old_len, start, len) = match (ranges.next(), ranges.next()) {
+            (Some(old), Some(new)) => {
+                let (_, old_len) = parse_range(&old[1..]);
+                let (start, len) = parse_range(&new[1..]);
+                (old_len, start, len)
+            }
+            _ => continue,
+        };
+
+        if len == 0 {
+            // removed lines are marked on the line next to them
+            if start == 0 {
+                changes.insert(1, Change::RemovedAbove);
+            } else {
+                changes.insert(start, Change::RemovedBelow);
+            }
+            continue;
+        }
+        let change = if old_len == 0 {
+            Change::Added
+        } else {
+            Change::Modified
+        };
+        for line in start..start + len {
+            changes.insert(line, change);
+        }
+    }
+    changes
+}
+
+/// Parses a range of a hunk header like `12,3`, where the length is 1 when
+/// it is left out.
+fn parse_range(range: &str) -> (usize, usize) {
+    let mut parts = range.splitn(2, ',').map(|n| n.parse().unwrap_or(0));
+    let start = parts.next().unwrap_or(0);
+    (start, parts.next().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hunks() {
+        let diff = "diff --git a/main.rs b/main.rs\n\
+                    --- a/main.rs\n\
+                    +++ b/main.rs\n\
+                    @@ -0,0 +1,2 @@\n\
+                    +use std::io;\n\
+                    +\n\
+                    @@ -5 +7 @@ fn main() {\n\
+                    -    a();\n\
+                    +    b();\n\
+                    @@ -9,2 +10,0 @@ fn main() {\n\
+                    -    c();\n\
+                    -    d();\n";
+        let changes = parse_diff(diff);
+        assert_eq!(changes.len(), 4);
+        assert_eq!(changes[&1], Change::Added);
+        assert_eq!(changes[&2], Change::Added);
+        assert_eq!(changes[&7], Change::Modified);
+        assert_eq!(changes[&10], Change::RemovedBelow);
+        assert_eq!(parse_diff("@@ -1,3 +0,0 @@\n")[&1], Change::RemovedAbove);
+    }
+}
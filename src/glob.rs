@@ -0,0 +1,176 @@
+/// A shell style wildcard pattern.
+///
+/// `*` matches any run of characters except `/`, `**` matches any run of
+/// characters including `/`, `?` matches a single character and `[...]`
+/// matches a character class such as `[a-z]` or `[!0-9]`. A backslash makes
+/// the following character literal.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+}
+
+#[derive(Clone, Debug)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    DoubleStar,
+    Class(bool, Vec<(char, char)>),
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Pattern {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    tokens.push(Token::DoubleStar);
+                    i += 2;
+                    // `**/` also matches no directory at all
+                    if chars.get(i) == Some(&'/') {
+                        i += 1;
+                    }
+                    continue;
+                }
+                '*' => tokens.push(Token::Star),
+                '?' => tokens.push(Token::Any),
+                '\\' if i + 1 < chars.len() => {
+                    i += 1;
+                    tokens.push(Token::Char(chars[i]));
+                }
+                '[' => match parse_class(&chars[i + 1..]) {
+                    Some((token, len)) => {
+                        tokens.push(token);
+                        i += len + 1;
+                        continue;
+                    }
+                    None => tokens.push(Token::Char('[')),
+                },
+                c => tokens.push(Token::Char(c)),
+            }
+            i += 1;
+        }
+        Pattern { tokens }
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        match_tokens(&self.tokens, &text)
+    }
+
+    /// Returns the number of characters which must appear literally, which
+    /// tells how specific the pattern is.
+    pub fn literal_len(&self) -> usize {
+        self.tokens
+            .iter()
+            .filter(|t| matches!(t, Token::Char(_)))
+            .count()
+    }
+}
+
+/// Parses a character class following `[`, returning the token and the
+/// number of characters consumed including the closing `]`.
+fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+    let mut i = 0;
+    let negated = match chars.first() {
+        Some(&'!') | Some(&'^') => {
+            i += 1;
+            true
+        }
+        _ => false,
+    };
+
+    let mut ranges = Vec::new();
+    let start = i;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == ']' && i > start {
+            return Some((Token::Class(negated, ranges), i + 1));
+        }
+        if chars.get(i + 1) == Some(&'-') && i + 2 < chars.len() && chars[i + 2] != ']' {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+    None
+}
+
+fn match_tokens(tokens: &[Token], text: &[char]) -> bool {
+    let (token, rest) = match tokens.split_first() {
+        Some(t) => t,
+        None => return text.is_empty(),
+    };
+
+    match *token {
+        Token::Star => (0..text.len() + 1)
+            .take_while(|&n| n == 0 || text[n - 1] != '/')
+            .any(|n| match_tokens(rest, &text[n..])),
+        Token::DoubleStar => (0..text.len() + 1).any(|n| match_tokens(rest, &text[n..])),
+        _ if text.is_empty() => false,
+        Token::Char(c) => c == text[0] && match_tokens(rest, &text[1..]),
+        Token::Any => text[0] != '/' && match_tokens(rest, &text[1..]),
+        Token::Class(negated, ref ranges) => {
+            let c = text[0];
+            let found = ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+            found != negated && c != '/' && match_tokens(rest, &text[1..])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! assert_match {
+        ( $pattern:expr, $( $text:expr ),* ) => (
+            $( assert!(Pattern::new($pattern).matches($text), "{} ~ {}", $pattern, $text); )*
+        );
+    }
+
+    macro_rules! assert_no_match {
+        ( $pattern:expr, $( $text:expr ),* ) => (
+            $( assert!(!Pattern::new($pattern).matches($text), "{} !~ {}", $pattern, $text); )*
+        );
+    }
+
+    #[test]
+    fn wildcards() {
+        assert_match!("*.rs", "main.rs", ".rs");
+        assert_no_match!("*.rs", "main.rs.bak", "src/main.rs");
+        assert_match!("Makefile.*", "Makefile.am", "Makefile.");
+        assert_no_match!("Makefile.*", "Makefile");
+        assert_match!("?.c", "a.c");
+        assert_no_match!("?.c", "ab.c", ".c");
+    }
+
+    #[test]
+    fn double_star() {
+        assert_match!("**/*.rs", "main.rs", "src/main.rs", "src/syntax/rule.rs");
+        assert_match!("src/**", "src/main.rs", "src/syntax/rule.rs");
+        assert_no_match!("src/**/*.rs", "tests/main.rs");
+    }
+
+    #[test]
+    fn classes() {
+        assert_match!("[a-c]x", "ax", "cx");
+        assert_no_match!("[a-c]x", "dx", "x");
+        assert_match!("[!a-c]x", "dx");
+        assert_no_match!("[!a-c]x", "ax");
+        assert_match!("[]]", "]");
+        assert_match!("[", "[");
+        assert_match!("\\*", "*");
+        assert_no_match!("\\*", "a");
+    }
+
+    #[test]
+    fn literal_len() {
+        assert_eq!(Pattern::new("Makefile.*").literal_len(), 9);
+        assert_eq!(Pattern::new("*.[ch]").literal_len(), 1);
+    }
+}
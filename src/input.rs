@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::str;
+
+/// The share of bytes in invalid UTF-8 sequences above which an input is
+/// considered binary.
+const MAX_INVALID_RATIO: f64 = 0.3;
+
+/// Suffixes of compressed files which are decompressed.
+static COMPRESSION_SUFFIXES: &[&str] = &["gz"];
+
+/// How far from the end of a file `tail_lines` looks for lines.
+const TAIL_BYTES: u64 = 4096;
+
+/// Returns up to `n` lines from the beginning of the input without consuming
+/// them. Only the bytes already buffered are looked at.
+pub fn peek_lines<R: BufRead>(input: &mut R, n: usize) -> io::Result<Vec<String>> {
+    let buf = input.fill_buf()?;
+    Ok(buf.split(|&b| b == b'\n')
+        .take(n)
+        .map(|line| String::from_utf8_lossy(line).into_owned())
+        .collect())
+}
+
+/// Reads up to `n` lines at the end of a file and rewinds it to the start.
+/// Files which cannot seek, like pipes, give no lines.
+pub fn tail_lines(file: &mut File, n: usize) -> Vec<String> {
+    let mut buf = Vec::new();
+    // a device or a growing file is read only up to the limit
+    let read = file.seek(SeekFrom::End(0))
+        .and_then(|len| file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES))))
+        .and_then(|_| file.by_ref().take(TAIL_BYTES).read_to_end(&mut buf));
+    // the file is rewound even when the seek or the read failed partway
+    let rewound = file.seek(SeekFrom::Start(0));
+    if read.is_err() || rewound.is_err() {
+        return Vec::new();
+    }
+
+    let text = String::from_utf8_lossy(&buf);
+    let mut lines: Vec<_> = text.lines().rev().take(n).map(String::from).collect();
+    lines.reverse();
+    lines
+}
+
+/// Returns the offset at which the last `n` complete lines of a file start,
+/// looking back from the end block by block. A last line without a newline is
+/// not counted.
+pub fn tail_offset(file: &mut File, n: usize) -> io::Result<u64> {
+    let mut end = file.seek(SeekFrom::End(0))?;
+    let mut newlines = 0;
+    let mut buf = vec![0; TAIL_BYTES as usize];
+    while end > 0 {
+        let start = end.saturating_sub(TAIL_BYTES);
+        let block = &mut buf[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(block)?;
+        // the lines start after the newline ending the line before them
+        for (i, _) in block.iter().enumerate().rev().filter(|&(_, &b)| b == b'\n') {
+            newlines += 1;
+            if newlines > n {
+                return Ok(start + i as u64 + 1);
+            }
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+/// Tells whether the beginning of an input looks like binary data, which is
+/// when it has a NUL byte or mostly is not UTF-8.
+pub fn is_binary(buf: &[u8]) -> bool {
+    if buf.contains(&0) {
+        return true;
+    }
+
+    let mut invalid = 0;
+    let mut rest = buf;
+    while let Err(e) = str::from_utf8(rest) {
+        match e.error_len() {
+            Some(len) => {
+                invalid += len;
+                rest = &rest[e.valid_up_to() + len..];
+            }
+            // a sequence cut off at the end of the buffer
+            None => break,
+        }
+    }
+    invalid as f64 > buf.len() as f64 * MAX_INVALID_RATIO
+}
+
+/// Tells whether an input starts with the magic number of gzip.
+pub fn is_gzip(buf: &[u8]) -> bool {
+    buf.starts_with(b"\x1F\x8B")
+}
+
+/// Strips a compression suffix like `.gz` off a path, so that the language
+/// of `dump.json.gz` is detected from `dump.json`.
+pub fn strip_compression(path: &Path) -> PathBuf {
+    match path.extension() {
+        Some(ext) if COMPRESSION_SUFFIXES.iter().any(|&s| ext == s) => path.with_extension(""),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Counts lines including the last one without a newline.
+pub fn count_lines(buf: &[u8]) -> usize {
+    let newlines = buf.iter().filter(|&&b| b == b'\n').count();
+    match buf.last() {
+        Some(&b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    #[test]
+    fn binary() {
+        assert!(!is_binary(b""));
+        assert!(!is_binary(b"fn main() {}\n"));
+        assert!(!is_binary("caf\u{e9}, \u{c5}ngstr\u{f6}m".as_bytes()));
+        // Latin-1 text has a few invalid bytes only
+        assert!(!is_binary(b"caf\xe9 au lait, cr\xe8me br\xfbl\xe9e"));
+        // a multibyte sequence cut off by the buffer
+        assert!(!is_binary(b"ab\xe2\x82"));
+        assert!(is_binary(b"ELF\x02\x01\x01\x00\x00"));
+        assert!(is_binary(b"\x89\xfe\xfa\xb3\xc0\xf8"));
+    }
+
+    #[test]
+    fn compression() {
+        assert!(is_gzip(b"\x1F\x8B\x08\x00"));
+        assert!(!is_gzip(b"\x1F"));
+        assert_eq!(strip_compression(Path::new("dump.json.gz")), Path::new("dump.json"));
+        assert_eq!(strip_compression(Path::new("main.rs")), Path::new("main.rs"));
+    }
+
+    #[test]
+    fn count() {
+        assert_eq!(count_lines(b""), 0);
+        assert_eq!(count_lines(b"a\n\n"), 2);
+        assert_eq!(count_lines(b"a\nb"), 2);
+    }
+
+    #[test]
+    fn tail_start() {
+        let path = env::temp_dir().join(format!("civet-tail-{}", process::id()));
+        let long = "x".repeat(TAIL_BYTES as usize * 2);
+        let offset = |text: &str, n| {
+            fs::write(&path, text).unwrap();
+            tail_offset(&mut File::open(&path).unwrap(), n).unwrap()
+        };
+        assert_eq!(offset("a\nb\nc\n", 2), 2);
+        assert_eq!(offset("a\nb\nc", 2), 0);
+        assert_eq!(offset("a\nb\nc\n", 5), 0);
+        assert_eq!(offset(&format!("a\n{}\nb\n", long), 2), 2);
+        assert_eq!(offset("", 2), 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn tail_of_device() {
+        // a device without an end is read only up to the limit
+        let mut file = File::open("/dev/zero").unwrap();
+        assert_eq!(tail_lines(&mut file, 5).len(), 1);
+        assert_eq!(file.stream_position().unwrap(), 0);
+    }
+}
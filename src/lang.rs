@@ -1,14 +1,239 @@
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::path::Path;
 use std::rc::Rc;
 
-use syntax::{load_grammar_from_source, Grammar};
+use error::Error;
+use glob::Pattern;
+use style::StyleTree;
+use syntax::regex::Regex;
+use syntax::str_piece::StrPiece;
+use syntax::{load_grammar_from_source, Grammar, Tokenizer};
 use _generated;
 
-pub fn identify(ext: &str) -> Option<&str> {
+lazy_static! {
+    static ref GLOB_LANGS: Vec<(Pattern, &'static str)> = _generated::GLOB_LANG_MAP
+        .iter()
+        .map(|&(pattern, scope)| (Pattern::new(pattern), scope))
+        .collect();
+
+    /// The `firstLineMatch` of grammars, compiled once they are first needed.
+    static ref FIRST_LINE_MATCHES: Vec<(&'static str, Result<Regex, Error>)> =
+        _generated::langs()
+            .iter()
+            .filter_map(|l| l.first_line_match.map(|expr| (l.scope_name, Regex::new(expr))))
+            .collect();
+}
+
+pub fn identify(ext: &str) -> Option<&'static str> {
     _generated::EXT_LANG_MAP.get(ext).cloned()
 }
 
+/// Languages of files set with `--map-syntax` and the config file, which
+/// take precedence over the file types of grammars.
+#[derive(Clone, Debug, Default)]
+pub struct SyntaxMapping {
+    // globs with a slash match whole paths, and others match file names
+    globs: Vec<(Pattern, bool, &'static str)>,
+}
+
+impl SyntaxMapping {
+    pub fn add(&mut self, glob: &str, scope: &'static str) {
+        self.globs
+            .push((Pattern::new(glob), glob.contains('/'), scope));
+    }
+
+    pub fn add_extension(&mut self, ext: &str, scope: &'static str) {
+        self.add(&format!("*.{}", ext.trim_start_matches('.')), scope);
+    }
+
+    /// Returns the language of the last mapping matching `path`.
+    fn find(&self, path: &Path, name: &str) -> Option<&'static str> {
+        self.globs
+            .iter()
+            .rev()
+            .find(|&&(ref pattern, whole_path, _)| {
+                if whole_path {
+                    pattern.matches(&path.to_string_lossy())
+                } else {
+                    pattern.matches(name)
+                }
+            })
+            .map(|&(_, _, scope)| scope)
+    }
+}
+
+/// Splits a mapping of `--map-syntax` like `*.conf:toml` into the glob and
+/// the language.
+pub fn split_mapping(mapping: &str) -> Result<(&str, &str), String> {
+    match mapping.rfind(':') {
+        Some(i) if i > 0 && i + 1 < mapping.len() => Ok((&mapping[..i], &mapping[i + 1..])),
+        _ => Err(format!("invalid mapping, expected glob:language: {}", mapping)),
+    }
+}
+
+/// The number of lines at each end of a file searched for modelines.
+pub const MODELINE_LINES: usize = 5;
+
+/// The number of lines at the beginning of a file used to guess its language.
+pub const GUESS_LINES: usize = 40;
+
+/// How many highlighted bytes a byte marked as invalid cancels out.
+const INVALID_PENALTY: isize = 10;
+
+/// The least score of a grammar to be guessed.
+const MIN_GUESS_SCORE: f64 = 0.3;
+
+/// How far the score of a guessed grammar has to be ahead of the next one.
+const MIN_GUESS_MARGIN: f64 = 0.1;
+
+/// Detects the language of an input from its path, if it has one, and the
+/// lines at the beginning and the end of its contents.
+///
+/// A modeline is looked for first since it is the most explicit, then the
+/// name of the file and the first line in turn.
+pub fn detect(
+    path: Option<&Path>,
+    head: &[String],
+    tail: &[String],
+    mapping: &SyntaxMapping,
+) -> Result<Option<&'static str>, Error> {
+    let scope = head.iter()
+        .chain(tail)
+        .filter_map(|line| detect_by_modeline(line))
+        .next()
+        .or_else(|| path.and_then(|path| detect_by_name(path, mapping)));
+    match (scope, head.first()) {
+        (None, Some(line)) => detect_by_first_line(line),
+        (scope, _) => Ok(scope),
+    }
+}
+
+/// Detects the language of a file from its name, by the mappings of users
+/// first.
+///
+/// A file type matches the whole name or the part following any dot, and a
+/// glob pattern matches the whole name. When several of them match, the most
+/// specific one, i.e. the one with the most literal characters, wins.
+pub fn detect_by_name(path: &Path, mapping: &SyntaxMapping) -> Option<&'static str> {
+    let name = path.file_name().and_then(|name| name.to_str())?;
+    if let Some(scope) = mapping.find(path, name) {
+        return Some(scope);
+    }
+
+    let mut candidates = Vec::new();
+    if let Some(scope) = identify(name) {
+        candidates.push((name.len() + 1, scope));
+    }
+    for (i, _) in name.match_indices('.') {
+        let suffix = &name[i + 1..];
+        if let Some(scope) = identify(suffix) {
+            candidates.push((suffix.len(), scope));
+        }
+    }
+    for &(ref pattern, scope) in GLOB_LANGS.iter() {
+        if pattern.matches(name) {
+            candidates.push((pattern.literal_len(), scope));
+        }
+    }
+
+    candidates
+        .into_iter()
+        .rev()
+        .max_by_key(|&(specificity, _)| specificity)
+        .map(|(_, scope)| scope)
+}
+
+/// Detects the language from the first line of a file, e.g. a shebang like
+/// `#!/usr/bin/env python3`, by the `firstLineMatch` of grammars. A pattern
+/// which cannot be compiled is an error of its grammar.
+pub fn detect_by_first_line(line: &str) -> Result<Option<&'static str>, Error> {
+    let line = StrPiece::new(line);
+    for &(scope, ref re) in FIRST_LINE_MATCHES.iter() {
+        match *re {
+            Ok(ref re) if re.find(line).is_some() => return Ok(Some(scope)),
+            Ok(_) => {}
+            Err(ref e) => return Err(Error::Grammar(format!("{}: {}", scope, e))),
+        }
+    }
+    Ok(None)
+}
+
+/// Detects the language from a Vim modeline like `# vim: set ft=toml:` or
+/// an Emacs one like `-*- mode: python -*-`.
+pub fn detect_by_modeline(line: &str) -> Option<&'static str> {
+    let vim = r"(?:^|\s)(?:vi|vim|Vim|ex)(?:[<=>]?\d+)?:\s*(.*)";
+    let emacs = r"-\*-\s*(.*?)\s*-\*-";
+
+    let name = if let Some(options) = capture(vim, line) {
+        options
+            .split(|c: char| c.is_whitespace() || c == ':')
+            .filter_map(|opt| {
+                let mut kv = opt.splitn(2, '=');
+                match (kv.next(), kv.next()) {
+                    (Some("ft"), v) | (Some("filetype"), v) => v,
+                    (Some("syn"), v) | (Some("syntax"), v) => v,
+                    _ => None,
+                }
+            })
+            .next()
+    } else if let Some(vars) = capture(emacs, line) {
+        if vars.contains(':') {
+            vars.split(';')
+                .filter_map(|var| {
+                    let mut kv = var.splitn(2, ':');
+                    match (kv.next(), kv.next()) {
+                        (Some(k), v) if k.trim().eq_ignore_ascii_case("mode") => v,
+                        _ => None,
+                    }
+                })
+                .next()
+        } else {
+            Some(vars)
+        }
+    } else {
+        None
+    };
+
+    // modes like `shell-script` or `makefile-gmake` are named after the
+    // languages before the dash
+    name.map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .and_then(|name| find(name).or_else(|| name.split('-').next().and_then(find)))
+}
+
+/// Returns the name of a language from its scope.
+pub fn name(scope: &str) -> Option<&'static str> {
+    _generated::langs()
+        .iter()
+        .find(|l| l.scope_name == scope)
+        .map(|l| l.name)
+}
+
+/// Finds the scope of a language given by its name, its scope name or one
+/// of its extensions.
+pub fn find(name: &str) -> Option<&'static str> {
+    let lower = name.to_lowercase();
+    _generated::langs()
+        .iter()
+        .find(|l| l.name.to_lowercase() == lower || l.scope_name == name)
+        .map(|l| l.scope_name)
+        .or_else(|| {
+            let ext = name.trim_start_matches('.');
+            identify(ext).or_else(|| identify(&ext.to_lowercase()))
+        })
+}
+
+/// Returns the first capture group of `expr` found in `text`.
+fn capture<'a>(expr: &str, text: &'a str) -> Option<&'a str> {
+    Regex::new(expr)
+        .ok()
+        .and_then(|re| re.find(StrPiece::new(text)))
+        .and_then(|m| m.captures.get(1).and_then(|&pos| pos))
+        .map(|(start, end)| &text[start..end])
+}
+
 pub struct LangLoader {
     grammars: RefCell<HashMap<String, Rc<Grammar>>>,
 }
@@ -20,19 +245,274 @@ impl LangLoader {
         }
     }
 
-    pub fn load_grammar(&self, lang: &str) -> Rc<Grammar> {
+    pub fn load_grammar(&self, lang: &str) -> Result<Rc<Grammar>, Error> {
         if let Some(g) = self.grammars.borrow().get(lang) {
-            return Rc::clone(g);
-        }
-        match load_grammar_from_source(lang) {
-            Ok(g) => {
-                let g = Rc::new(g);
-                self.grammars
-                    .borrow_mut()
-                    .insert(lang.to_owned(), Rc::clone(&g));
-                g
+            return Ok(Rc::clone(g));
+        }
+        let g = Rc::new(load_grammar_from_source(lang)?);
+        self.grammars
+            .borrow_mut()
+            .insert(lang.to_owned(), Rc::clone(&g));
+        Ok(g)
+    }
+
+    /// Guesses the language of `lines` by tokenizing them with every grammar.
+    /// A grammar scores the share of non-blank bytes to which it gives scopes
+    /// colored by `theme`, beyond the share it gives them in plain prose, and
+    /// bytes marked as invalid count against it. The best grammar wins if its
+    /// score reaches `MIN_GUESS_SCORE` and no other comes close, and grammars
+    /// which fail to load are left out. Returns the scope and the score.
+    pub fn guess(&self, lines: &[String], theme: &StyleTree) -> Option<(&'static str, f64)> {
+        if lines.iter().all(|line| non_blank_len(line) == 0) {
+            return None;
+        }
+        let prose: Vec<String> = PROSE.lines().map(String::from).collect();
+
+        let mut scores = Vec::new();
+        for lang in _generated::langs().iter() {
+            let grammar = match self.load_grammar(lang.scope_name) {
+                Ok(grammar) => grammar,
+                Err(_) => continue,
+            };
+            let baseline = highlighted_share(&grammar, &prose, theme).max(0.0);
+            let share = highlighted_share(&grammar, lines, theme);
+            let score = (share - baseline) / (1.0 - baseline).max(MIN_GUESS_SCORE);
+            scores.push((lang.scope_name, score));
+        }
+        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        let best = *scores.first()?;
+        let runner_up = scores.get(1).map_or(0.0, |&(_, score)| score);
+        Some(best).filter(|&(_, score)| {
+            score >= MIN_GUESS_SCORE && score - runner_up >= MIN_GUESS_MARGIN
+        })
+    }
+}
+
+/// Plain English, which grammars with few keywords highlight in parts, like
+/// capitalized words as type names. The share a grammar highlights in it is
+/// no evidence of the language.
+static PROSE: &str = "\
+Hi Anna,
+Thanks for the quick reply. The build failed again on Monday, at the same
+step as last week. Could you and Tom look at the logs? They are in the shared
+folder. If the fix is ready by Friday, we can still ship the release in May.
+Best regards, Ben";
+
+/// Returns the share of the non-blank bytes of `lines` which `grammar` gives
+/// a scope colored by `theme`, less the bytes marked as invalid.
+fn highlighted_share(grammar: &Rc<Grammar>, lines: &[String], theme: &StyleTree) -> f64 {
+    let mut tokenizer = Tokenizer::new(grammar);
+    let mut total = 0;
+    let mut scoped: isize = 0;
+    let mut previous = None;
+    for line in lines {
+        let line = format!("{}\n", line);
+        total += non_blank_len(&line);
+        for token in tokenizer.tokenize_line(&line) {
+            let text = &line[token.start..token.end];
+            let len = non_blank_len(text) as isize;
+            let rest = &line[token.end..];
+            if token.scopes.iter().any(|s| s.starts_with("invalid")) {
+                scoped -= INVALID_PENALTY * len;
+            } else if is_highlighted(&token.scopes, text, rest, previous.as_deref(), theme) {
+                scoped += len;
+            }
+            if len > 0 {
+                previous = token.scopes.last().cloned();
             }
-            Err(e) => panic!("{}", e),
         }
     }
+    if total == 0 {
+        return 0.0;
+    }
+    scoped as f64 / total as f64
+}
+
+/// Scopes which grammars give to nearly anything, like plain identifiers.
+static GENERIC_SCOPES: &[&str] = &["meta.", "source.", "variable.other"];
+
+/// Scopes of text which may have any characters in it, unlike keywords and
+/// names.
+static FREE_TEXT_SCOPES: &[&str] = &["comment.", "string.", "constant.character.", "markup."];
+
+/// Characters which end keywords and names in about every language.
+static NAME_BREAKS: &[char] = &['(', ')', '[', ']', '{', '}', ',', ';'];
+
+/// Whether `theme` colors a token by its scopes other than the generic ones,
+/// where `rest` is the text of the line after it and `previous` the scope of
+/// the token before it.
+///
+/// Only what tells languages apart counts. Grammars wrap large parts of code
+/// in `meta` scopes, and highlight about any operator and any function call,
+/// while a function is told from a call by the keyword defining it. A
+/// capitalized word other than a keyword is taken for a type or a constant
+/// whatever it is, and a keyword or a name with brackets in it is a region a
+/// grammar failed to end, like an import running to the end of the input.
+fn is_highlighted(
+    scopes: &[String],
+    text: &str,
+    rest: &str,
+    previous: Option<&str>,
+    theme: &StyleTree,
+) -> bool {
+    let scopes: Vec<&String> = scopes
+        .iter()
+        .skip(1)
+        .filter(|s| !GENERIC_SCOPES.iter().any(|g| s.starts_with(g)))
+        .collect();
+    let leaf = match scopes.last() {
+        Some(leaf) => leaf,
+        None => return false,
+    };
+    if scopes.iter().all(|s| theme.get(s).is_empty()) {
+        return false;
+    }
+    if scopes.iter().any(|s| FREE_TEXT_SCOPES.iter().any(|f| s.starts_with(f))) {
+        return true;
+    }
+
+    let text = text.trim();
+    if !text.contains(char::is_alphanumeric) || text.contains(NAME_BREAKS) {
+        return false;
+    }
+    if leaf.starts_with("keyword.") {
+        return true;
+    }
+    if rest.starts_with('(') {
+        let defined =
+            previous.is_some_and(|s| s.starts_with("keyword.") || s.starts_with("storage."));
+        return leaf.contains(".builtin") || leaf.starts_with("entity.name.function") && defined;
+    }
+    !text.starts_with(char::is_uppercase)
+}
+
+fn non_blank_len(s: &str) -> usize {
+    s.bytes().filter(|b| !b.is_ascii_whitespace()).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use theme;
+
+    fn by_name(name: &str) -> Option<&'static str> {
+        detect_by_name(Path::new(name), &SyntaxMapping::default())
+    }
+
+    #[test]
+    fn detect_name() {
+        assert_eq!(by_name("src/main.rs"), Some("source.rust"));
+        assert_eq!(by_name("Makefile"), Some("source.makefile"));
+        assert_eq!(by_name("Makefile.am"), Some("source.makefile"));
+        assert_eq!(by_name("app.Dockerfile"), Some("source.dockerfile"));
+        assert_eq!(by_name("/home/civet/.bashrc"), Some("source.shell"));
+        assert_eq!(by_name("CMakeLists.txt"), Some("source.cmake"));
+        assert_eq!(by_name("Cargo.lock"), Some("source.toml"));
+        assert_eq!(by_name("go.mod"), Some("source.gomod"));
+        assert_eq!(by_name("README"), None);
+    }
+
+    #[test]
+    fn syntax_mapping() {
+        let mut mapping = SyntaxMapping::default();
+        mapping.add_extension(".rsx", "source.rust");
+        mapping.add_extension("cfg.json", "source.toml");
+        mapping.add("*.conf", "source.toml");
+        mapping.add("**/shell/*.conf", "source.shell");
+        mapping.add("Jenkinsfile", "source.java");
+        let by_name = |name| detect_by_name(Path::new(name), &mapping);
+        assert_eq!(by_name("app.rsx"), Some("source.rust"));
+        assert_eq!(by_name("app.cfg.json"), Some("source.toml"));
+        assert_eq!(by_name("app.json"), Some("source.json"));
+        assert_eq!(by_name("etc/app.conf"), Some("source.toml"));
+        assert_eq!(by_name("etc/shell/app.conf"), Some("source.shell"));
+        assert_eq!(by_name("ci/Jenkinsfile"), Some("source.java"));
+
+        assert_eq!(split_mapping("*.conf:toml"), Ok(("*.conf", "toml")));
+        assert!(split_mapping("*.conf").is_err());
+        assert!(split_mapping(":toml").is_err());
+    }
+
+    #[test]
+    fn detect_first_line() {
+        let first_line = |line| detect_by_first_line(line).unwrap();
+        assert_eq!(first_line("#!/usr/bin/env python3"), Some("source.python"));
+        assert_eq!(first_line("#!/bin/bash -e"), Some("source.shell"));
+        assert_eq!(first_line("#!/usr/bin/env node"), Some("source.js"));
+        assert_eq!(first_line("#!/usr/bin/fish"), None);
+        for &(scope, ref re) in FIRST_LINE_MATCHES.iter() {
+            assert!(re.is_ok(), "{}", scope);
+        }
+    }
+
+    #[test]
+    fn detect_modeline() {
+        let modeline = detect_by_modeline;
+        assert_eq!(modeline("# vim: set ft=toml:"), Some("source.toml"));
+        assert_eq!(modeline("/* vim: set ts=4 filetype=cpp: */"), Some("source.cpp"));
+        assert_eq!(modeline("// vi:syntax=javascript"), Some("source.js"));
+        assert_eq!(modeline("# -*- mode: python -*-"), Some("source.python"));
+        assert_eq!(modeline("# -*- coding: utf-8; Mode: shell-script -*-"), Some("source.shell"));
+        assert_eq!(modeline("/* -*- C++ -*- */"), Some("source.cpp"));
+        assert_eq!(modeline("navi: ft=toml"), None);
+        assert_eq!(modeline("# -*- coding: utf-8 -*-"), None);
+    }
+
+    #[test]
+    fn guess() {
+        let lines = |s: &str| s.lines().map(String::from).collect::<Vec<_>>();
+        let theme = theme::load(theme::default());
+        let loader = LangLoader::new();
+        let guess = |s: &str| loader.guess(&lines(s), &theme).map(|(scope, _)| scope);
+
+        assert_eq!(guess("def f(a):\n    return a + 1\n"), Some("source.python"));
+        assert_eq!(
+            guess(
+                "import os\nimport sys\n\ndef main():\n    print(os.getcwd(), sys.argv)\n\n\
+                 if __name__ == '__main__':\n    main()\n"
+            ),
+            Some("source.python")
+        );
+        // too short to tell Python from Go, whose grammar scopes `getcwd` as a library call
+        assert!(guess("import os\nprint(os.getcwd())\n").is_none_or(|s| s == "source.python"));
+        assert_eq!(
+            guess("package main\n\nimport \"fmt\"\n\nfunc main() {\n\tfmt.Println(\"hi\")\n}\n"),
+            Some("source.go")
+        );
+        assert_eq!(
+            guess("fn main() {\n    let v = vec![1, 2];\n    println!(\"{:?}\", v);\n}\n"),
+            Some("source.rust")
+        );
+        // JavaScript and Java color JSON as much as its own grammar does
+        assert_eq!(guess("{\n  \"a\": [1, null]\n}\n"), None);
+        // there is no grammar of SQL, which is not taken for Java either
+        assert_eq!(guess("SELECT * FROM t;\n"), None);
+        assert_eq!(
+            guess("SELECT name, count(*) FROM users WHERE id > 3 GROUP BY name;\n"),
+            None
+        );
+        assert_eq!(guess("Hello team,\nplease look at the ticket.\n"), None);
+        assert_eq!(
+            guess("It is about the build, which fails on Mondays.\nThanks, Anna\n"),
+            None
+        );
+        assert_eq!(guess(""), None);
+    }
+
+    #[test]
+    fn detect_order() {
+        let lines = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let rs = Some(Path::new("x.rs"));
+        let tmpl = Some(Path::new("config.tmpl"));
+        let detect = |path, head: &[String], tail: &[String]| {
+            detect(path, head, tail, &SyntaxMapping::default()).unwrap()
+        };
+
+        assert_eq!(detect(rs, &lines(&["#!/bin/sh"]), &[]), Some("source.rust"));
+        assert_eq!(detect(tmpl, &lines(&["#!/bin/sh"]), &[]), Some("source.shell"));
+        assert_eq!(
+            detect(rs, &lines(&["x = 1"]), &lines(&["# vim: ft=toml"])),
+            Some("source.toml")
+        );
+    }
 }
@@ -4,15 +4,20 @@
 extern crate atty;
 #[macro_use]
 extern crate clap;
+extern crate flate2;
 #[macro_use]
 extern crate lazy_static;
+#[cfg(unix)]
+extern crate libc;
 extern crate onig;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate unicode_width;
 
 mod lazy;
+mod glob;
 mod lang;
 mod theme;
 mod app;
@@ -20,6 +25,17 @@ mod syntax;
 mod style;
 mod colorizer;
 mod civet;
+mod input;
+mod encoding;
+mod writer;
+mod diff_writer;
+mod pager;
+mod range;
+mod term;
+mod git;
+mod walk;
+mod config;
+mod diff;
 mod error;
 mod _generated;
 
@@ -0,0 +1,159 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::{env, mem};
+
+use term;
+
+/// The pager used when neither `CIVET_PAGER` nor `PAGER` is set.
+const DEFAULT_PAGER: &str = "less -RFX";
+
+/// The output of civet, which is stdout or the stdin of a pager.
+pub enum Output {
+    Stdout(io::Stdout),
+    /// Output held back until it is taller than the screen, when the pager
+    /// is started with it. Rows are counted by newlines, since long lines are
+    /// wrapped by the writer on a terminal.
+    Held {
+        command: Vec<String>,
+        buf: Vec<u8>,
+        rows: usize,
+        height: usize,
+    },
+    Pager(Child),
+}
+
+impl Output {
+    pub fn stdout() -> Output {
+        Output::Stdout(io::stdout())
+    }
+
+    /// Starts the pager of `CIVET_PAGER` or `PAGER`, falling back to stdout
+    /// when it cannot be started. Unless `always` is true, the pager is
+    /// started only once the output is taller than the screen, so that short
+    /// output goes to stdout with any pager.
+    pub fn pager(always: bool) -> Output {
+        let command = env::var("CIVET_PAGER")
+            .or_else(|_| env::var("PAGER"))
+            .ok()
+            .filter(|c| !c.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_PAGER.to_owned());
+        let mut output = Output::Held {
+            command: pager_command(&command),
+            buf: Vec::new(),
+            rows: 0,
+            height: term::height(),
+        };
+        if always {
+            // nothing is held yet to be written
+            let _ = output.start();
+        }
+        output
+    }
+
+    /// Starts the pager of held output and writes the output to it.
+    fn start(&mut self) -> io::Result<()> {
+        let (command, buf) = match mem::replace(self, Output::stdout()) {
+            Output::Held { command, buf, .. } => (command, buf),
+            output => {
+                *self = output;
+                return Ok(());
+            }
+        };
+        let child = Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .spawn();
+        if let Ok(child) = child {
+            *self = Output::Pager(child);
+        }
+        self.inner().write_all(&buf)
+    }
+
+    /// Closes the output and waits until the pager quits. Held output is
+    /// written to stdout.
+    pub fn finish(self) {
+        match self {
+            Output::Held { buf, .. } => {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(&buf).and_then(|_| stdout.flush());
+            }
+            Output::Pager(mut child) => {
+                drop(child.stdin.take());
+                let _ = child.wait();
+            }
+            Output::Stdout(_) => {}
+        }
+    }
+
+    fn inner(&mut self) -> &mut dyn Write {
+        match *self {
+            Output::Stdout(ref mut stdout) => stdout,
+            Output::Held { ref mut buf, .. } => buf,
+            Output::Pager(ref mut child) => child.stdin.as_mut().unwrap(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Output::Held {
+            buf: ref mut held,
+            ref mut rows,
+            height,
+            ..
+        } = *self
+        {
+            held.extend_from_slice(buf);
+            *rows += buf.iter().filter(|&&b| b == b'\n').count();
+            if *rows < height {
+                return Ok(buf.len());
+            }
+        } else {
+            return self.inner().write(buf);
+        }
+        self.start()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner().flush()
+    }
+}
+
+/// Splits the command of a pager into words. less is given `-RFX` without
+/// arguments, and `-R` with arguments lacking it, since it shows escape
+/// sequences raw otherwise.
+fn pager_command(command: &str) -> Vec<String> {
+    let mut words: Vec<String> = command.split_whitespace().map(String::from).collect();
+    let is_less = Path::new(&words[0]).file_name() == Some("less".as_ref());
+    if is_less && words.len() == 1 {
+        words.extend(DEFAULT_PAGER.split_whitespace().skip(1).map(String::from));
+    } else if is_less && !words[1..].iter().any(|w| is_raw_flag(w)) {
+        words.push("-R".to_owned());
+    }
+    words
+}
+
+/// Tells whether an argument of less lets escape sequences through.
+fn is_raw_flag(arg: &str) -> bool {
+    match arg {
+        "--RAW-CONTROL-CHARS" | "--raw-control-chars" => true,
+        _ if arg.starts_with("--") => false,
+        _ => arg.starts_with('-') && arg.contains(&['R', 'r'][..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn less_flags() {
+        assert_eq!(pager_command("less"), ["less", "-RFX"]);
+        assert_eq!(pager_command("/usr/bin/less -S"), ["/usr/bin/less", "-S", "-R"]);
+        assert_eq!(pager_command("less -SR"), ["less", "-SR"]);
+        assert_eq!(pager_command("less --raw-control-chars"), ["less", "--raw-control-chars"]);
+        assert_eq!(pager_command("more -d"), ["more", "-d"]);
+    }
+}
@@ -0,0 +1,125 @@
+/// Lines of each input to print, selected by `--line-range`, `--head` and
+/// `--tail`. Lines are numbered from 1 and a line is printed when any of the
+/// ranges contains it. No ranges at all select every line.
+#[derive(Clone, Debug, Default)]
+pub struct LineRanges {
+    ranges: Vec<(usize, usize)>,
+    tail: Option<usize>,
+}
+
+impl LineRanges {
+    /// Selects no line, so that lines are only counted.
+    pub fn none() -> LineRanges {
+        LineRanges {
+            ranges: vec![(usize::MAX, usize::MAX)],
+            tail: None,
+        }
+    }
+
+    /// Adds a range of the form `N`, `N:M`, `:M` or `N:`.
+    pub fn add(&mut self, range: &str) -> Result<(), String> {
+        self.ranges.push(parse(range)?);
+        Ok(())
+    }
+
+    pub fn add_head(&mut self, n: usize) {
+        self.ranges.push((1, n));
+    }
+
+    pub fn add_tail(&mut self, n: usize) {
+        self.tail = Some(self.tail.map_or(n, |t| t.max(n)));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty() && self.tail.is_none()
+    }
+
+    /// Tells whether the number of lines has to be known to select them.
+    pub fn needs_count(&self) -> bool {
+        self.tail.is_some()
+    }
+
+    /// Resolves `--tail` into a range for an input of `count` lines.
+    pub fn with_count(&self, count: usize) -> LineRanges {
+        let mut ranges = self.ranges.clone();
+        if let Some(n) = self.tail {
+            ranges.push((count.saturating_sub(n) + 1, usize::MAX));
+        }
+        LineRanges { ranges, tail: None }
+    }
+
+    pub fn contains(&self, line: usize) -> bool {
+        self.is_empty() || self.ranges.iter().any(|&(s, e)| s <= line && line <= e)
+    }
+
+    /// Tells whether no line after `line` is selected, so the rest of the
+    /// input can be left unread.
+    pub fn is_past_end(&self, line: usize) -> bool {
+        self.tail.is_none() && !self.ranges.is_empty()
+            && self.ranges.iter().all(|&(_, e)| e <= line)
+    }
+}
+
+pub fn parse(range: &str) -> Result<(usize, usize), String> {
+    let number = |s: &str, default| {
+        if s.is_empty() {
+            return Ok(default);
+        }
+        match s.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("invalid line number: {}", s)),
+        }
+    };
+    let (start, end) = match range.find(':') {
+        Some(i) => (number(&range[..i], 1)?, number(&range[i + 1..], usize::MAX)?),
+        None => {
+            let n = number(range, 0)?;
+            (n, n)
+        }
+    };
+    if range.is_empty() || range == ":" || start > end {
+        return Err(format!("invalid line range: {}", range));
+    }
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(parse("120:180"), Ok((120, 180)));
+        assert_eq!(parse(":40"), Ok((1, 40)));
+        assert_eq!(parse("300:"), Ok((300, usize::MAX)));
+        assert_eq!(parse("7"), Ok((7, 7)));
+        assert!(parse("").is_err());
+        assert!(parse(":").is_err());
+        assert!(parse("0:3").is_err());
+        assert!(parse("5:3").is_err());
+        assert!(parse("a:3").is_err());
+    }
+
+    #[test]
+    fn select_lines() {
+        let mut lines = LineRanges::default();
+        assert!(lines.contains(1) && lines.contains(1000));
+        assert!(!lines.is_past_end(1000));
+
+        lines.add(":2").unwrap();
+        lines.add("5").unwrap();
+        assert!(lines.contains(2) && !lines.contains(3) && lines.contains(5));
+        assert!(!lines.is_past_end(4) && lines.is_past_end(5));
+    }
+
+    #[test]
+    fn tail() {
+        let mut lines = LineRanges::default();
+        lines.add_head(1);
+        lines.add_tail(2);
+        assert!(lines.needs_count());
+        let lines = lines.with_count(10);
+        assert!(lines.contains(1) && !lines.contains(8) && lines.contains(9));
+        assert!(!lines.is_past_end(9));
+    }
+}
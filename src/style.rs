@@ -20,6 +20,14 @@ struct Theme {
     // semantic_class: String,
     // color_space_name: String,
     token_colors: Vec<TokenColor>,
+    #[serde(default)]
+    colors: HashMap<String, usize>,
+    gutter_settings: Option<GutterSettings>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GutterSettings {
+    foreground: Option<usize>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -44,11 +52,14 @@ pub struct RawStyle {
     foreground: Option<usize>,
     background: Option<usize>,
     font_style: Option<String>,
+    invisibles: Option<usize>,
+    line_highlight: Option<usize>,
 }
 
 pub struct StyleTree {
     root: Node,
     default_style: Style,
+    colors: HashMap<String, usize>,
 }
 
 impl StyleTree {
@@ -56,14 +67,33 @@ impl StyleTree {
         StyleTree {
             root: Node::new(Style::empty()),
             default_style: Style::empty(),
+            colors: HashMap::new(),
         }
     }
 
     pub fn create(text: &str) -> Result<StyleTree> {
         let theme: Theme = serde_json::from_str(text)?;
         let mut tree = StyleTree::new();
+        tree.colors = theme.colors;
+        if let Some(fg) = theme.gutter_settings.and_then(|g| g.foreground) {
+            tree.colors
+                .entry("editorLineNumber.foreground".to_owned())
+                .or_insert(fg);
+        }
         for token_color in &theme.token_colors {
             if token_color.scope.is_none() {
+                // tmTheme-style global settings name some editor colors
+                if let Some(invisibles) = token_color.style.invisibles {
+                    tree.colors
+                        .entry("editorWhitespace.foreground".to_owned())
+                        .or_insert(invisibles);
+                }
+                if let Some(line_highlight) = token_color.style.line_highlight {
+                    tree.colors
+                        .entry("editor.lineHighlightBackground".to_owned())
+                        .or_insert(line_highlight);
+                }
+
                 // set default style
                 let mut style = Style::from(token_color.style.clone());
                 style.bg = None; // disable default background
@@ -109,6 +139,53 @@ impl StyleTree {
         }
         self.default_style.overlap(&style)
     }
+
+    /// Looks up an editor color of the theme such as `editor.background`.
+    pub fn color(&self, key: &str) -> Option<usize> {
+        self.colors.get(key).cloned()
+    }
+
+    /// Style of the notations for invisible characters like `^I` and `$`.
+    pub fn invisibles(&self) -> Style {
+        let mut style = self.default_style.clone();
+        if let Some(fg) = self.color("editorWhitespace.foreground") {
+            style.fg = Some(fg);
+        }
+        style
+    }
+
+    /// Style of line numbers and borders around them.
+    pub fn gutter(&self) -> Style {
+        let mut style = self.invisibles();
+        if let Some(fg) = self.color("editorLineNumber.foreground") {
+            style.fg = Some(fg);
+        }
+        style
+    }
+
+    /// Style of a git change marker in the gutter, whose color is named by
+    /// `key` like `editorGutter.addedBackground`.
+    pub fn change_marker(&self, key: &str) -> Style {
+        let mut style = self.gutter();
+        if let Some(fg) = self.color(key) {
+            style.fg = Some(fg);
+        }
+        style
+    }
+
+    /// Background of highlighted lines, which goes under token styles.
+    pub fn line_highlight(&self) -> Option<Style> {
+        self.background("editor.lineHighlightBackground")
+    }
+
+    /// Style with the color named by `key` as the background only.
+    pub fn background(&self, key: &str) -> Option<Style> {
+        self.color(key).map(|bg| Style {
+            fg: None,
+            bg: Some(bg),
+            fs: None,
+        })
+    }
 }
 
 struct Node {
@@ -199,13 +276,13 @@ impl Style {
     pub fn overlap(&self, style: &Style) -> Style {
         let mut new = self.clone();
         if style.fg.is_some() {
-            new.fg = style.fg.clone();
+            new.fg = style.fg;
         }
         if style.bg.is_some() {
-            new.bg = style.bg.clone();
+            new.bg = style.bg;
         }
         if style.fs.is_some() {
-            new.fs = style.fs.clone();
+            new.fs = style.fs;
         }
         new
     }
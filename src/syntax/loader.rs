@@ -1,15 +1,22 @@
+use std::result;
+
+use error::Error;
 use syntax::raw_rule::RawRule;
 
 use _generated;
 
+type Result<T> = result::Result<T, Error>;
+
 pub trait Loader {
-    fn load(&self, &str) -> Option<RawRule>;
+    fn load(&self, &str) -> Result<RawRule>;
 }
 
 pub struct GlobalSourceLoader;
 
 impl Loader for GlobalSourceLoader {
-    fn load(&self, source_name: &str) -> Option<RawRule> {
-        RawRule::from_str(_generated::retrieve_syntax(source_name)).ok()
+    fn load(&self, source_name: &str) -> Result<RawRule> {
+        let source = _generated::retrieve_syntax(source_name)
+            .ok_or_else(|| Error::Grammar(format!("undefined language source: {}", source_name)))?;
+        RawRule::from_str(source).map_err(|e| Error::Grammar(format!("{}: {}", source_name, e)))
     }
 }
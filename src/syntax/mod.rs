@@ -5,16 +5,16 @@ pub mod str_piece;
 pub mod tokenizer;
 pub mod loader;
 
-use std::io::Result;
+use std::result;
+
+use error::Error;
 use self::loader::{GlobalSourceLoader, Loader};
 pub use self::rule::{Grammar, GrammarBuilder};
 pub use self::tokenizer::Tokenizer;
 
-pub fn load_grammar_from_source(src_name: &str) -> Result<Grammar> {
+pub fn load_grammar_from_source(src_name: &str) -> result::Result<Grammar, Error> {
     let loader = Box::new(GlobalSourceLoader);
-    let rule = loader
-        .load(src_name)
-        .expect(&format!("undefined language source: {}", src_name));
+    let rule = loader.load(src_name)?;
     let mut c = GrammarBuilder::new(rule, loader);
-    Ok(c.build())
+    c.build()
 }
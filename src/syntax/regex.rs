@@ -1,4 +1,5 @@
 use onig::{self, RegexOptions, Region, SearchOptions, Syntax};
+use error::Error;
 use syntax::str_piece::StrPiece;
 
 pub struct Regex {
@@ -6,11 +7,14 @@ pub struct Regex {
 }
 
 impl Regex {
-    pub fn new(pattern: &str) -> Regex {
+    pub fn new(pattern: &str) -> Result<Regex, Error> {
         let option = RegexOptions::REGEX_OPTION_NONE;
-        let re = onig::Regex::with_options_and_encoding(pattern, option, Syntax::default())
-            .expect(&format!("cannot compile pattern: {}", pattern));
-        Regex { re }
+        onig::Regex::with_options_and_encoding(pattern, option, Syntax::default())
+            .map(|re| Regex { re })
+            .map_err(|e| Error::Regex {
+                pattern: pattern.to_owned(),
+                message: e.description().to_owned(),
+            })
     }
 
     pub fn find<'a>(&self, text: StrPiece<'a>) -> Option<MatchResult> {
@@ -73,21 +77,29 @@ mod tests {
         let haystack = StrPiece::new("abc 1234");
 
         // the begin of substring of line may not be the begin of line.
-        let re = Regex::new("^");
+        let re = Regex::new("^").unwrap();
         assert_eq!(find(&re, haystack), pv![p!(0, 0)]);
         assert_eq!(find(&re, haystack.substr(0, 5)), pv![p!(0, 0)]);
         assert_eq!(find(&re, haystack.substr(4, 3)), None);
 
         // the end of substring of line may not be the end of line.
-        let re = Regex::new("$");
+        let re = Regex::new("$").unwrap();
         assert_eq!(find(&re, haystack), pv![p!(8, 8)]);
         assert_eq!(find(&re, haystack.substr(5, 3)), pv![p!(8, 8)]);
         assert_eq!(find(&re, haystack.substr(4, 3)), None);
 
         // capture positions must be in range of substring
-        let re = Regex::new("12");
+        let re = Regex::new("12").unwrap();
         assert_eq!(find(&re, haystack.substr(3, 2)), None);
         assert_eq!(find(&re, haystack.substr(3, 3)), pv![p!(4, 6)]);
-        assert_eq!(find(&Regex::new("(?!)\\G"), haystack.substr(3, 0)), None);
+        assert_eq!(find(&Regex::new("(?!)\\G").unwrap(), haystack.substr(3, 0)), None);
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        match Regex::new("(unclosed") {
+            Err(Error::Regex { pattern, .. }) => assert_eq!(pattern, "(unclosed"),
+            _ => panic!("pattern compiled"),
+        }
     }
 }
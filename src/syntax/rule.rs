@@ -1,6 +1,8 @@
 use std::rc::{Rc, Weak};
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::result;
+use error::Error;
 use syntax::regex::{self, Regex};
 use syntax::str_piece::StrPiece;
 use syntax::raw_rule::{RawCapture, RawRule, RawRuleRef, WeakRawRuleRef};
@@ -9,6 +11,8 @@ use lazy::Lazy;
 
 pub type RuleId = usize;
 
+type Result<T> = result::Result<T, Error>;
+
 pub struct MatchResult {
     pub rule: RuleId,
     pub caps: regex::MatchResult,
@@ -253,36 +257,36 @@ impl GrammarBuilder {
         }
     }
 
-    fn get_source(&mut self, source: &str) -> RawRuleRef {
+    fn get_source(&mut self, source: &str) -> Result<RawRuleRef> {
         if let Some(src) = self.sources.get(source) {
-            return src.clone();
+            return Ok(src.clone());
         }
 
-        let raw = self.loader.load(source).unwrap();
+        let raw = self.loader.load(source)?;
         let src = self.sources
             .entry(source.to_owned())
             .or_insert(RawRuleRef::new(raw));
-        src.clone()
+        Ok(src.clone())
     }
 
-    pub fn build(&mut self) -> Grammar {
+    pub fn build(&mut self) -> Result<Grammar> {
         let src = self.src_rule.clone();
         let ctx = Context::new(src.to_weak(), src.to_weak());
-        let root = self.compile_rule(src, &ctx);
+        let root = self.compile_rule(src, &ctx)?;
 
-        Grammar {
+        Ok(Grammar {
             rules: (0..self.next_id).map(|i| self.rules[&i].clone()).collect(),
             root_id: root.id(),
-        }
+        })
     }
 
-    fn create_rule(&mut self, rule_id: RuleId, rule: RawRuleRef, ctx: &Context) -> Inner {
-        if rule.match_expr.is_some() {
+    fn create_rule(&mut self, rule_id: RuleId, rule: RawRuleRef, ctx: &Context) -> Result<Inner> {
+        let inner = if rule.match_expr.is_some() {
             Inner::Match(MatchRule {
                 id: rule_id,
                 name: rule.name.clone(),
-                expr: Regex::new(rule.match_expr.as_ref().unwrap()),
-                captures: self.compile_captures(&rule.captures, ctx),
+                expr: Regex::new(rule.match_expr.as_ref().unwrap())?,
+                captures: self.compile_captures(&rule.captures, ctx)?,
             })
         } else if rule.begin.is_none() {
             let name = if rule.scope_name.is_some() {
@@ -294,9 +298,9 @@ impl GrammarBuilder {
             let patterns = if rule.repository.is_some() {
                 let mut ctx = ctx.clone();
                 ctx.st.push(rule.to_weak());
-                self.compile_patterns(&rule.patterns, &ctx)
+                self.compile_patterns(&rule.patterns, &ctx)?
             } else {
-                self.compile_patterns(&rule.patterns, ctx)
+                self.compile_patterns(&rule.patterns, ctx)?
             };
 
             Inner::Include(IncludeRule {
@@ -308,35 +312,39 @@ impl GrammarBuilder {
             Inner::BeginWhile(BeginWhileRule {
                 id: rule_id,
                 name: rule.name.clone(),
-                begin_expr: Regex::new(rule.begin.as_ref().unwrap()),
+                begin_expr: Regex::new(rule.begin.as_ref().unwrap())?,
                 while_expr: rule.while_expr.clone().unwrap(),
-                begin_captures: self.compile_captures(&rule.begin_captures, ctx),
-                patterns: self.compile_patterns(&rule.patterns, ctx),
+                begin_captures: self.compile_captures(&rule.begin_captures, ctx)?,
+                patterns: self.compile_patterns(&rule.patterns, ctx)?,
             })
         } else {
             Inner::BeginEnd(BeginEndRule {
                 id: rule_id,
                 name: rule.name.clone(),
                 content_name: rule.content_name.clone(),
-                begin_expr: Regex::new(rule.begin.as_ref().unwrap()),
-                end_expr: rule.end.clone().unwrap(),
-                begin_captures: self.compile_captures(&rule.begin_captures, ctx),
-                end_captures: self.compile_captures(&rule.end_captures, ctx),
-                patterns: self.compile_patterns(&rule.patterns, ctx),
+                begin_expr: Regex::new(rule.begin.as_ref().unwrap())?,
+                end_expr: rule.end
+                    .clone()
+                    .ok_or_else(|| Error::Grammar("begin pattern without end".to_owned()))?,
+                begin_captures: self.compile_captures(&rule.begin_captures, ctx)?,
+                end_captures: self.compile_captures(&rule.end_captures, ctx)?,
+                patterns: self.compile_patterns(&rule.patterns, ctx)?,
             })
-        }
+        };
+        Ok(inner)
     }
-    fn compile_rule(&mut self, raw: RawRuleRef, ctx: &Context) -> Rule {
+
+    fn compile_rule(&mut self, raw: RawRuleRef, ctx: &Context) -> Result<Rule> {
         match raw.id.get() {
-            Some(rule_id) => self.rules[&rule_id].clone(),
+            Some(rule_id) => Ok(self.rules[&rule_id].clone()),
             None => {
                 let rule_id = self.next_rule_id();
                 raw.id.set(Some(rule_id));
 
                 let rule = Rule::new();
                 self.rules.insert(rule_id, rule.clone());
-                rule.assign(self.create_rule(rule_id, raw, ctx));
-                rule
+                rule.assign(self.create_rule(rule_id, raw, ctx)?);
+                Ok(rule)
             }
         }
     }
@@ -345,12 +353,12 @@ impl GrammarBuilder {
         &mut self,
         patterns: &Option<Vec<RawRuleRef>>,
         ctx: &Context,
-    ) -> Vec<WeakRule> {
+    ) -> Result<Vec<WeakRule>> {
         let mut compiled_patterns = Vec::new();
         if let Some(ref patterns) = *patterns {
             for pattern in patterns {
                 let rule = match pattern.include {
-                    None => self.compile_rule(pattern.clone(), ctx),
+                    None => self.compile_rule(pattern.clone(), ctx)?,
                     Some(ref inc) if inc == "$base" => {
                         let base = ctx.base.upgrade().unwrap();
                         self.rules[base.id.get().as_ref().unwrap()].clone()
@@ -365,48 +373,54 @@ impl GrammarBuilder {
                         let pat = reference_sources[1];
 
                         if source.is_empty() {
-                            self.compile_reference(pat, ctx)
+                            self.compile_reference(pat, ctx)?
                         } else {
-                            let new_root = self.get_source(source);
+                            let new_root = self.get_source(source)?;
                             let ctx = Context::new(ctx._self.clone(), new_root.to_weak());
-                            self.compile_reference(pat, &ctx)
+                            self.compile_reference(pat, &ctx)?
                         }
                     }
                     Some(ref inc) => {
-                        let new_root = self.get_source(inc);
+                        let new_root = self.get_source(inc)?;
                         let ctx = Context::new(ctx._self.clone(), new_root.to_weak());
-                        self.compile_rule(ctx._self.upgrade().unwrap(), &ctx)
+                        self.compile_rule(ctx._self.upgrade().unwrap(), &ctx)?
                     }
                 };
                 compiled_patterns.push(rule.to_weak());
             }
         }
-        compiled_patterns
+        Ok(compiled_patterns)
     }
 
-    fn compile_reference(&mut self, name: &str, ctx: &Context) -> Rule {
-        self.compile_rule(ctx.search_pattern(name), &ctx)
+    fn compile_reference(&mut self, name: &str, ctx: &Context) -> Result<Rule> {
+        let raw = ctx.search_pattern(name)?;
+        self.compile_rule(raw, &ctx)
     }
 
-    fn compile_captures(&mut self, captures: &Option<RawCapture>, ctx: &Context) -> CaptureGroup {
+    fn compile_captures(
+        &mut self,
+        captures: &Option<RawCapture>,
+        ctx: &Context,
+    ) -> Result<CaptureGroup> {
         let mut h = HashMap::new();
         if let Some(ref captures) = *captures {
             match *captures {
                 RawCapture::Map(ref map) => for (k, v) in map {
-                    let r = self.compile_rule(v.clone(), ctx).to_weak();
-                    let n = k.parse::<usize>().unwrap();
+                    let r = self.compile_rule(v.clone(), ctx)?.to_weak();
+                    let n = k.parse::<usize>()
+                        .map_err(|_| Error::Grammar(format!("invalid capture number: {}", k)))?;
                     h.insert(n, r);
                 },
                 RawCapture::List(ref list) => {
                     if list.is_empty() {
-                        panic!("Empty capture list")
+                        return Err(Error::Grammar("empty capture list".to_owned()));
                     }
-                    let r = self.compile_rule((&list[0]).clone(), ctx).to_weak();
+                    let r = self.compile_rule((&list[0]).clone(), ctx)?.to_weak();
                     h.insert(0, r);
                 }
             }
         }
-        CaptureGroup(h)
+        Ok(CaptureGroup(h))
     }
 
     fn next_rule_id(&mut self) -> RuleId {
@@ -432,16 +446,12 @@ impl Context {
         }
     }
 
-    fn search_pattern(&self, pat: &str) -> RawRuleRef {
+    fn search_pattern(&self, pat: &str) -> Result<RawRuleRef> {
         for rule in self.st.iter().map(|r| r.upgrade().unwrap()) {
-            let repo = rule.repository
-                .as_ref()
-                .expect("broken format: repository not found");
-
-            if let Some(found) = repo.get(pat) {
-                return found.clone();
+            if let Some(found) = rule.repository.as_ref().and_then(|repo| repo.get(pat)) {
+                return Ok(found.clone());
             }
         }
-        panic!("pattern \"{}\" not found in the repository", pat);
+        Err(Error::Include(format!("#{}", pat)))
     }
 }
@@ -203,7 +203,9 @@ impl RuleState {
         let scopes = vec![rule.name().map(String::from)];
         RuleState {
             rule,
-            expr: expr.map(|s| Regex::new(&s)),
+            // an end pattern which does not compile once its back references
+            // are replaced never matches, so the rule lasts to the end
+            expr: expr.and_then(|s| Regex::new(&s).ok()),
             scopes,
         }
     }
@@ -320,6 +322,7 @@ fn replace_backref<'a>(mut s: String, text: StrPiece<'a>, m: &regex::MatchResult
 mod tests {
     use super::*;
 
+    use error::Error;
     use syntax::raw_rule::RawRule;
     use syntax::loader::Loader;
     use syntax::rule::GrammarBuilder;
@@ -346,19 +349,22 @@ mod tests {
     struct EmptySourceLoader;
 
     impl Loader for EmptySourceLoader {
-        fn load(&self, _: &str) -> Option<RawRule> {
-            None
+        fn load(&self, name: &str) -> Result<RawRule, Error> {
+            Err(Error::Grammar(format!("undefined language source: {}", name)))
         }
     }
 
-    fn tokenizer(rule_text: &str) -> Tokenizer {
+    fn build(rule_text: &str) -> Result<Grammar, Error> {
         let rule_text = format!(r#"{{ "patterns": [{}] }}"#, rule_text);
         let rawrule = RawRule::from_str(&rule_text);
         assert!(rawrule.is_ok());
 
         let rawrule = rawrule.unwrap();
-        let grammar = GrammarBuilder::new(rawrule, Box::new(EmptySourceLoader)).build();
-        let grammar = Rc::new(grammar);
+        GrammarBuilder::new(rawrule, Box::new(EmptySourceLoader)).build()
+    }
+
+    fn tokenizer(rule_text: &str) -> Tokenizer {
+        let grammar = Rc::new(build(rule_text).unwrap());
         Tokenizer::new(&grammar)
     }
 
@@ -492,7 +498,7 @@ mod tests {
 
     #[test]
     fn backref() {
-        let re = Regex::new("#IF_(\\w+)");
+        let re = Regex::new("#IF_(\\w+)").unwrap();
         let s = StrPiece::new("   #IF_BLOCK   ");
         assert_eq!(
             &replace_backref("#END_\\1".into(), s, &re.find(s).unwrap()),
@@ -500,7 +506,8 @@ mod tests {
         );
 
         let re =
-            Regex::new("LOCATION:\\s*([\\w\\d]+)\\s*,\\s*([\\w\\d]+)\\s*,\\s*([\\w\\d]+)\\s*;");
+            Regex::new("LOCATION:\\s*([\\w\\d]+)\\s*,\\s*([\\w\\d]+)\\s*,\\s*([\\w\\d]+)\\s*;")
+                .unwrap();
         let s = StrPiece::new("LOCATION: Jeju, Ulsan, Seoul;");
         assert_eq!(
             &replace_backref(
@@ -511,4 +518,19 @@ mod tests {
             "I lived in Jeju, Ulsan, and Seoul!"
         );
     }
+    #[test]
+    fn broken_grammar() {
+        let error = |rule_text| match build(rule_text) {
+            Ok(_) => "none",
+            Err(Error::Regex { .. }) => "regex",
+            Err(Error::Include(_)) => "include",
+            Err(Error::Grammar(_)) => "grammar",
+            Err(_) => "other",
+        };
+        assert_eq!(error(r#"{ "match": "(a" }"#), "regex");
+        assert_eq!(error(r##"{ "include": "#missing" }"##), "include");
+        assert_eq!(error(r#"{ "include": "source.missing" }"#), "grammar");
+        assert_eq!(error(r#"{ "begin": "a", "end": "b", "beginCaptures": [] }"#), "grammar");
+        assert_eq!(error(r#"{ "begin": "a" }"#), "grammar");
+    }
 }
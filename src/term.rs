@@ -0,0 +1,56 @@
+use std::env;
+#[cfg(unix)]
+use std::mem;
+
+#[cfg(unix)]
+use libc;
+
+/// Width used when neither the terminal nor `COLUMNS` tells it.
+const DEFAULT_WIDTH: usize = 80;
+
+/// Height used when neither the terminal nor `LINES` tells it.
+const DEFAULT_HEIGHT: usize = 24;
+
+/// Returns the number of columns of the terminal on stdout, or of `COLUMNS`
+/// when stdout is not a terminal.
+pub fn width() -> usize {
+    window_size()
+        .map(|(columns, _)| columns)
+        .filter(|&c| c > 0)
+        .unwrap_or_else(|| from_env("COLUMNS", DEFAULT_WIDTH))
+}
+
+/// Returns the number of rows of the terminal on stdout, or of `LINES` when
+/// stdout is not a terminal.
+pub fn height() -> usize {
+    window_size()
+        .map(|(_, rows)| rows)
+        .filter(|&r| r > 0)
+        .unwrap_or_else(|| from_env("LINES", DEFAULT_HEIGHT))
+}
+
+/// Returns the columns and rows of the terminal on stdout.
+#[cfg(unix)]
+fn window_size() -> Option<(usize, usize)> {
+    let mut size: libc::winsize = unsafe { mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    if ok {
+        Some((size.ws_col as usize, size.ws_row as usize))
+    } else {
+        None
+    }
+}
+
+/// Other systems fall back to the environment.
+#[cfg(not(unix))]
+fn window_size() -> Option<(usize, usize)> {
+    None
+}
+
+fn from_env(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .filter(|&c| c > 0)
+        .unwrap_or(default)
+}
@@ -0,0 +1,315 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
+
+/// Files in each directory which list paths to leave out.
+static IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+/// Walks directories for `--recursive`, filtering files with `--include` and
+/// `--exclude` and leaving out what `.gitignore` and `.ignore` ignore.
+/// Symbolic links to directories are followed, except into a directory which
+/// has been walked already, so that a cycle of links ends.
+pub struct Walker {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+/// A line of an ignore file.
+struct IgnoreRule {
+    // the directory of the ignore file relative to the root of the walk
+    base: PathBuf,
+    // the root of the walk relative to the directory of an ignore file above
+    // it, which is empty for ignore files in the walk
+    prefix: PathBuf,
+    pattern: Pattern,
+    anchored: bool,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Walker {
+    pub fn new(include: &[String], exclude: &[String]) -> Walker {
+        Walker {
+            include: include.iter().map(|p| Pattern::new(p)).collect(),
+            exclude: exclude.iter().map(|p| Pattern::new(p)).collect(),
+        }
+    }
+
+    /// Returns the files under `root` sorted by path, with the files of a
+    /// directory in the place of its name. Directories which cannot be read
+    /// give errors in their places. The ignore files of the directories above
+    /// `root` up to the root of its git repository apply too.
+    pub fn walk(&self, root: &Path) -> Vec<io::Result<PathBuf>> {
+        let mut files = Vec::new();
+        let mut rules = ancestor_rules(root);
+        let mut visited = HashSet::new();
+        self.walk_dir(root, Path::new(""), &mut rules, &mut visited, &mut files);
+        files
+    }
+
+    fn walk_dir(
+        &self,
+        root: &Path,
+        rel: &Path,
+        rules: &mut Vec<IgnoreRule>,
+        visited: &mut HashSet<PathBuf>,
+        files: &mut Vec<io::Result<PathBuf>>,
+    ) {
+        let dir = root.join(rel);
+        if let Ok(real) = fs::canonicalize(&dir) {
+            if !visited.insert(real) {
+                return;
+            }
+        }
+        let entries = fs::read_dir(&dir).and_then(|rd| rd.collect::<io::Result<Vec<_>>>());
+        let mut entries = match entries {
+            Ok(entries) => entries,
+            Err(e) => {
+                let msg = format!("{}: {}", dir.display(), e);
+                files.push(Err(io::Error::new(e.kind(), msg)));
+                return;
+            }
+        };
+        entries.sort_by_key(|e| e.file_name());
+
+        let rule_count = rules.len();
+        for name in IGNORE_FILES {
+            rules.extend(read_ignore_file(&dir.join(name), rel, Path::new("")));
+        }
+
+        for entry in entries {
+            let name = entry.file_name();
+            if name == ".git" {
+                continue;
+            }
+            let path = rel.join(&name);
+            // symbolic links are followed to what they point to
+            let is_dir = fs::metadata(dir.join(&name))
+                .map(|m| m.is_dir())
+                .unwrap_or(false);
+            if is_ignored(rules, &path, is_dir) || matches_any(&self.exclude, &path) {
+                continue;
+            }
+            if is_dir {
+                self.walk_dir(root, &path, rules, visited, files);
+            } else if self.include.is_empty() || matches_any(&self.include, &path) {
+                files.push(Ok(root.join(&path)));
+            }
+        }
+        rules.truncate(rule_count);
+    }
+}
+
+/// Matches patterns against the file name and the path relative to the root
+/// of the walk.
+fn matches_any(patterns: &[Pattern], path: &Path) -> bool {
+    let rel = path.to_string_lossy();
+    let name = path.file_name().map_or(rel.clone(), |n| n.to_string_lossy());
+    patterns.iter().any(|p| p.matches(&name) || p.matches(&rel))
+}
+
+/// Returns the rules of the ignore files in the directories above `root` up
+/// to the root of its git repository, after those of `.git/info/exclude`.
+/// Outside of a repository there are none.
+fn ancestor_rules(root: &Path) -> Vec<IgnoreRule> {
+    let root = match fs::canonicalize(root) {
+        Ok(root) => root,
+        Err(_) => return Vec::new(),
+    };
+    let repo = match root.ancestors().find(|dir| dir.join(".git").exists()) {
+        Some(repo) => repo,
+        None => return Vec::new(),
+    };
+    let prefix = |dir: &Path| root.strip_prefix(dir).unwrap().to_path_buf();
+
+    let exclude = repo.join(".git").join("info").join("exclude");
+    let mut rules = read_ignore_file(&exclude, Path::new(""), &prefix(repo));
+    let dirs: Vec<&Path> = root.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(repo))
+        .collect();
+    for dir in dirs.into_iter().rev() {
+        for name in IGNORE_FILES {
+            rules.extend(read_ignore_file(&dir.join(name), Path::new(""), &prefix(dir)));
+        }
+    }
+    rules
+}
+
+/// Reads the rules of an ignore file. Anything but a regular file, like a
+/// directory of the same name, has none.
+fn read_ignore_file(path: &Path, base: &Path, prefix: &Path) -> Vec<IgnoreRule> {
+    if !fs::metadata(path).map(|m| m.is_file()).unwrap_or(false) {
+        return Vec::new();
+    }
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| parse_rule(&line, base))
+        .map(|rule| IgnoreRule {
+            prefix: prefix.to_path_buf(),
+            ..rule
+        })
+        .collect()
+}
+
+/// Parses a line of `.gitignore`.
+fn parse_rule(line: &str, base: &Path) -> Option<IgnoreRule> {
+    let mut line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let negated = line.starts_with('!');
+    if negated {
+        line = &line[1..];
+    }
+    let dir_only = line.ends_with('/');
+    if dir_only {
+        line = &line[..line.len() - 1];
+    }
+    // a slash at the beginning or in the middle anchors the pattern
+    let anchored = line.contains('/');
+    let line = line.trim_start_matches('/');
+    if line.is_empty() {
+        return None;
+    }
+    Some(IgnoreRule {
+        base: base.to_path_buf(),
+        prefix: PathBuf::new(),
+        pattern: Pattern::new(line),
+        anchored,
+        negated,
+        dir_only,
+    })
+}
+
+/// Tells whether the last rule matching a path ignores it.
+fn is_ignored(rules: &[IgnoreRule], path: &Path, is_dir: bool) -> bool {
+    for rule in rules.iter().rev() {
+        if rule.dir_only && !is_dir {
+            continue;
+        }
+        let rel = match path.strip_prefix(&rule.base) {
+            Ok(rel) => rel,
+            Err(_) => continue,
+        };
+        let matched = if rule.anchored {
+            rule.pattern.matches(&rule.prefix.join(rel).to_string_lossy())
+        } else {
+            path.file_name()
+                .is_some_and(|name| rule.pattern.matches(&name.to_string_lossy()))
+        };
+        if matched {
+            return !rule.negated;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn rules(lines: &[&str]) -> Vec<IgnoreRule> {
+        lines
+            .iter()
+            .filter_map(|l| parse_rule(l, Path::new("")))
+            .collect()
+    }
+
+    #[test]
+    fn ignore_rules() {
+        let rules = rules(&["# comment", "", "*.o", "!keep.o", "/target", "build/", "doc/*.html"]);
+        assert_eq!(rules.len(), 5);
+
+        let ignored = |path: &str, is_dir| is_ignored(&rules, Path::new(path), is_dir);
+        assert!(ignored("main.o", false));
+        assert!(ignored("src/main.o", false));
+        assert!(!ignored("src/keep.o", false));
+        assert!(ignored("target", true));
+        assert!(!ignored("src/target", true));
+        assert!(ignored("src/build", true));
+        assert!(!ignored("build", false));
+        assert!(ignored("doc/index.html", false));
+        assert!(!ignored("src/doc/index.html", false));
+        assert!(!ignored("main.rs", false));
+    }
+
+    #[test]
+    fn nested_rules() {
+        let mut rules = rules(&["*.log"]);
+        rules.extend(parse_rule("!debug.log", Path::new("src")));
+        assert!(is_ignored(&rules, Path::new("debug.log"), false));
+        assert!(!is_ignored(&rules, Path::new("src/debug.log"), false));
+    }
+
+    #[test]
+    fn rules_above_root() {
+        // rules of the ignore file of the parent of a walk of src/
+        let rules: Vec<_> = rules(&["/src/gen", "*.tmp"])
+            .into_iter()
+            .map(|rule| IgnoreRule {
+                prefix: PathBuf::from("src"),
+                ..rule
+            })
+            .collect();
+        assert!(is_ignored(&rules, Path::new("gen"), true));
+        assert!(!is_ignored(&rules, Path::new("lib/gen"), true));
+        assert!(is_ignored(&rules, Path::new("lib/a.tmp"), false));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn walk_repository() {
+        use std::os::unix::fs::symlink;
+
+        let tmp = env::temp_dir().join(format!("civet-walk-{}", process::id()));
+        let _ = fs::remove_dir_all(&tmp);
+        fs::create_dir_all(tmp.join(".git").join("info")).unwrap();
+        fs::create_dir_all(tmp.join("sub")).unwrap();
+        fs::create_dir_all(tmp.join("other")).unwrap();
+        fs::write(tmp.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(tmp.join(".git").join("info").join("exclude"), "*.bak\n").unwrap();
+        for name in &["sub/a.rs", "sub/b.log", "sub/c.bak", "other/d.rs"] {
+            fs::write(tmp.join(name), "").unwrap();
+        }
+        symlink("../other", tmp.join("sub").join("link")).unwrap();
+        symlink(".", tmp.join("sub").join("loop")).unwrap();
+
+        let root = tmp.join("sub");
+        let files: Vec<PathBuf> = Walker::new(&[], &[])
+            .walk(&root)
+            .into_iter()
+            .map(|f| f.unwrap())
+            .collect();
+        fs::remove_dir_all(&tmp).unwrap();
+        assert_eq!(files, [root.join("a.rs"), root.join("link/d.rs")]);
+    }
+
+    #[test]
+    fn ignore_file_directory() {
+        let tmp = env::temp_dir().join(format!("civet-ignore-dir-{}", process::id()));
+        let _ = fs::remove_dir_all(&tmp);
+        fs::create_dir_all(tmp.join(".gitignore")).unwrap();
+        fs::write(tmp.join("a.rs"), "").unwrap();
+
+        // reading a directory fails every time, which must not read forever
+        let rules = read_ignore_file(&tmp.join(".gitignore"), Path::new(""), Path::new(""));
+        let files: Vec<PathBuf> = Walker::new(&[], &[])
+            .walk(&tmp)
+            .into_iter()
+            .map(|f| f.unwrap())
+            .collect();
+        fs::remove_dir_all(&tmp).unwrap();
+        assert!(rules.is_empty());
+        assert_eq!(files, [tmp.join("a.rs")]);
+    }
+}
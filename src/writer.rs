@@ -0,0 +1,943 @@
+use std::borrow::Cow;
+use std::cmp;
+use std::io::{self, BufRead, Write};
+use std::{result, str};
+
+use atty;
+use unicode_width::UnicodeWidthChar;
+
+use civet::{Options, Wrap};
+use colorizer::LineColorizer;
+use error::Error;
+use range::LineRanges;
+use git::{Change, LineChanges};
+use style::{Style, StyleTree};
+use term;
+use theme;
+
+type Result<T> = result::Result<T, Error>;
+
+/// U+FFFD in UTF-8.
+const REPLACEMENT: &[u8] = b"\xEF\xBF\xBD";
+
+/// Width of the line numbers of `--style=numbers` with the space after them.
+const NUMBER_WIDTH: usize = 6;
+
+/// Width of the git change markers of `--style=changes`.
+const CHANGE_WIDTH: usize = 2;
+
+/// Distance between the tab stops of terminals.
+pub const TAB_WIDTH: usize = 8;
+
+/// Marks the rows a long line wraps into.
+pub const CONTINUATION_MARKER: &str = "↪";
+
+/// The size of an input in its header.
+#[derive(Clone, Copy)]
+pub enum Size {
+    Bytes(u64),
+    /// The size of a compressed file, since the contents are decompressed
+    /// as they are written.
+    Compressed(u64),
+}
+
+/// Writes the contents of inputs in the manner of cat.
+///
+/// A single writer is shared by all inputs, so line numbers and squeezed
+/// blank lines carry over from one file to the next, except that the numbers
+/// of `--style=numbers` start over for each input.
+pub struct Writer<'a, W: Write> {
+    inner: W,
+    options: &'a Options,
+    line_num: usize,
+    // the index of the last line read from the input, which `write` keeps
+    // counting when an input is written in parts
+    line_index: usize,
+    prev_blank: bool,
+    line_start: bool,
+    theme: Option<StyleTree>,
+    changes: LineChanges,
+    width: usize,
+    wrap: Wrap,
+    // the screen column of the output and where the text of a row starts
+    column: usize,
+    text_start: usize,
+    highlight: Option<Style>,
+}
+
+impl<'a, W: Write> Writer<'a, W> {
+    pub fn new(inner: W, options: &'a Options) -> Writer<'a, W> {
+        let theme = if options.colored {
+            Some(theme::load(options.theme))
+        } else {
+            None
+        };
+        Writer {
+            inner,
+            options,
+            line_num: 1,
+            line_index: 0,
+            prev_blank: false,
+            line_start: true,
+            theme,
+            changes: LineChanges::new(),
+            width: term::width(),
+            wrap: match options.wrap {
+                Wrap::Auto if !atty::is(atty::Stream::Stdout) => Wrap::Never,
+                wrap => wrap,
+            },
+            column: 0,
+            text_start: 0,
+            highlight: None,
+        }
+    }
+
+    /// Writes the header and the top border of an input for `--style`.
+    /// `changes` are marked in the gutter of the lines of the input.
+    pub fn begin_input(
+        &mut self,
+        name: &str,
+        language: Option<&str>,
+        size: Option<Size>,
+        changes: LineChanges,
+    ) -> Result<()> {
+        if self.options.decorations.numbers {
+            self.line_num = 1;
+        }
+        self.line_index = 0;
+        self.changes = changes;
+        self.write_header(name, language, size).map_err(Error::Output)
+    }
+
+    fn write_header(
+        &mut self,
+        name: &str,
+        language: Option<&str>,
+        size: Option<Size>,
+    ) -> io::Result<()> {
+        let decorations = self.options.decorations;
+        if decorations.grid {
+            self.write_rule('┬')?;
+        }
+        if decorations.header {
+            let mut details: Vec<String> = language.into_iter().map(String::from).collect();
+            details.extend(size.map(|size| match size {
+                Size::Bytes(len) => human_size(len),
+                Size::Compressed(len) => format!("{} compressed", human_size(len)),
+            }));
+            if decorations.grid && self.has_gutter() {
+                let border = format!("{:1$}│ ", "", self.gutter_width());
+                let gutter = self.theme.as_ref().map(|t| t.gutter());
+                self.write_marker(&border, gutter.as_ref())?;
+            }
+            match self.theme {
+                Some(_) => write!(self.inner, "File: \x1B[1m{}{}", name, Style::reset())?,
+                None => write!(self.inner, "File: {}", name)?,
+            }
+            if !details.is_empty() {
+                write!(self.inner, " ({})", details.join(", "))?;
+            }
+            self.inner.write_all(b"\n")?;
+            if decorations.grid {
+                self.write_rule('┼')?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the bottom border of an input for `--style`.
+    pub fn end_input(&mut self) -> Result<()> {
+        let decorations = self.options.decorations;
+        if decorations.is_plain() {
+            return Ok(());
+        }
+        if !self.line_start {
+            self.inner.write_all(b"\n").map_err(Error::Output)?;
+            self.line_start = true;
+        }
+        if decorations.grid {
+            self.write_rule('┴').map_err(Error::Output)?;
+        }
+        self.inner.flush().map_err(Error::Output)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Writes the lines of an input in `lines`. Lines out of the ranges are
+    /// still colorized to keep the state of the tokenizer, and counted for
+    /// line numbers. An input may be written in parts, whose lines are
+    /// counted on from the part before.
+    pub fn write<R: BufRead>(
+        &mut self,
+        mut reader: R,
+        mut lc: Option<&mut LineColorizer>,
+        lines: &LineRanges,
+    ) -> Result<()> {
+        while !lines.is_past_end(self.line_index) {
+            let mut line = Vec::new();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            self.line_index += 1;
+            let line_index = self.line_index;
+
+            let blank_line = self.line_start && line == b"\n";
+            if !lines.contains(line_index) {
+                if let Some(ref mut lc) = lc {
+                    lc.process_line(&self.tokenizer_text(&line).0);
+                }
+                if self.is_numbered(blank_line) {
+                    self.line_num += 1;
+                }
+                self.prev_blank = false;
+                self.line_start = line.ends_with(b"\n");
+                continue;
+            }
+            if self.options.squeeze_blank && self.prev_blank && blank_line {
+                continue;
+            }
+            self.prev_blank = blank_line;
+
+            let lc = lc.as_deref_mut();
+            self.write_line(line, line_index, blank_line, lc)
+                .map_err(Error::Output)?;
+        }
+        self.inner.flush().map_err(Error::Output)
+    }
+
+    /// Writes a line in the ranges with its prefix, colorized with `lc`.
+    fn write_line(
+        &mut self,
+        line: Vec<u8>,
+        line_index: usize,
+        blank_line: bool,
+        mut lc: Option<&mut LineColorizer>,
+    ) -> io::Result<()> {
+        if self.line_start {
+            let number = if self.is_numbered(blank_line) {
+                self.line_num += 1;
+                Some(self.line_num - 1)
+            } else {
+                None
+            };
+            self.write_prefix(number, line_index)?;
+        }
+
+        // invalid UTF-8 is decoded lossily for colorizing, and written as
+        // it is otherwise or when every byte of it has a notation of `-v`
+        let line = match lc {
+            Some(_) if !self.options.show_nonprinting => match String::from_utf8_lossy(&line) {
+                Cow::Borrowed(_) => line,
+                Cow::Owned(text) => text.into_bytes(),
+            },
+            _ => line,
+        };
+
+        let newline = line.ends_with(b"\n");
+        let mut content_len = line.len() - if newline { 1 } else { 0 };
+        let mut end_marker = "$";
+        if self.options.show_ends && line.ends_with(b"\r\n") {
+            // a carriage return is shown as part of the end of line
+            content_len -= 1;
+            end_marker = "^M$";
+        }
+
+        // a highlighted line is painted under the styles of its tokens
+        let highlight = self.theme
+            .as_ref()
+            .and_then(|t| t.line_highlight())
+            .filter(|_| !self.options.highlight_lines.is_empty())
+            .filter(|_| self.options.highlight_lines.contains(line_index));
+        let paint = |style: Style| match highlight {
+            Some(ref hl) => hl.overlap(&style),
+            None => style,
+        };
+        self.highlight = highlight.clone();
+
+        match lc.as_mut() {
+            Some(lc) => {
+                let (text, offsets) = self.tokenizer_text(&line);
+                let marker = paint(lc.invisibles());
+                let mut pos = 0;
+                let mut text_pos = 0;
+                for (style, token) in lc.process_line(&text) {
+                    text_pos += token.len();
+                    let next = offsets.partition_point(|&offset| offset < text_pos);
+                    let end = cmp::min(next, content_len);
+                    if pos < end {
+                        // in the auto mode, a token which does not fit in
+                        // the rest of the row goes to the next row
+                        let width = self.text_width(&line[pos..end]);
+                        if self.wrap == Wrap::Auto
+                            && width <= self.width.saturating_sub(self.text_start)
+                            && self.needs_break(width)
+                        {
+                            self.break_row(None)?;
+                        }
+                        self.write_text(&line[pos..end], Some((&paint(style), &marker)))?;
+                    }
+                    pos = cmp::max(pos, next);
+                }
+            }
+            None => match highlight {
+                Some(ref hl) => self.write_text(&line[..content_len], Some((hl, hl)))?,
+                None => self.write_text(&line[..content_len], None)?,
+            },
+        }
+
+        if newline {
+            if self.options.show_ends {
+                let marker = lc.as_ref()
+                    .map(|lc| lc.invisibles())
+                    .or_else(|| highlight.clone())
+                    .map(&paint);
+                if self.needs_break(end_marker.len()) {
+                    self.break_row(None)?;
+                }
+                self.write_marker(end_marker, marker.as_ref())?;
+            }
+            self.end_row()?;
+        }
+        self.highlight = None;
+        self.line_start = newline;
+        Ok(())
+    }
+
+    /// Returns a line as the tokenizer reads it, with the offset in the text
+    /// of each byte of the line and of its end. Under `-v`, bytes above ASCII
+    /// are read as their notations, and a line is valid UTF-8 otherwise.
+    fn tokenizer_text(&self, line: &[u8]) -> (String, Vec<usize>) {
+        if !self.options.show_nonprinting {
+            let text = String::from_utf8_lossy(line).into_owned();
+            return (text, (0..=line.len()).collect());
+        }
+        let mut text = String::new();
+        let mut offsets = Vec::with_capacity(line.len() + 1);
+        for &b in line {
+            offsets.push(text.len());
+            match self.notation(b) {
+                Some(ref notation) if b >= 0x80 => text.push_str(notation),
+                _ => text.push(b as char),
+            }
+        }
+        offsets.push(text.len());
+        (text, offsets)
+    }
+
+    fn is_numbered(&self, blank_line: bool) -> bool {
+        self.line_start && (self.options.display_number || self.options.decorations.numbers)
+            && !(self.options.number_nonblank && blank_line)
+    }
+
+    /// Tells whether lines are prefixed with the gutter of `--style` rather
+    /// than with the line numbers of cat.
+    fn has_gutter(&self) -> bool {
+        self.options.decorations.numbers || self.options.decorations.changes
+    }
+
+    fn has_number_column(&self) -> bool {
+        self.options.decorations.numbers || self.options.display_number
+    }
+
+    fn gutter_width(&self) -> usize {
+        let mut width = 0;
+        if self.has_number_column() {
+            width += NUMBER_WIDTH;
+        }
+        if self.options.decorations.changes {
+            width += CHANGE_WIDTH;
+        }
+        width
+    }
+
+    /// Writes the line number of cat, or the gutter of `--style`.
+    fn write_prefix(&mut self, number: Option<usize>, line_index: usize) -> io::Result<()> {
+        if !self.has_gutter() {
+            let number = number.map(|n| format!("{:6}", n));
+            return self.write_cat_prefix(number, None);
+        }
+
+        let gutter = self.theme.as_ref().map(|t| t.gutter());
+        if self.has_number_column() {
+            let column = match number {
+                Some(n) => format!("{:1$} ", n, NUMBER_WIDTH - 1),
+                None => format!("{:1$}", "", NUMBER_WIDTH),
+            };
+            self.write_marker(&column, gutter.as_ref())?;
+        }
+        self.write_gutter_rest(self.changes.get(&line_index).cloned())
+    }
+
+    /// Writes the prefix of a row which a long line wraps into, which has the
+    /// continuation marker in place of the line number.
+    fn write_continuation(&mut self) -> io::Result<()> {
+        let gutter = self.theme.as_ref().map(|t| t.gutter());
+        if !self.has_gutter() {
+            let marker = format!("{:>6}", CONTINUATION_MARKER);
+            let number = Some(marker).filter(|_| self.options.display_number);
+            return self.write_cat_prefix(number, gutter.as_ref());
+        }
+
+        if self.has_number_column() {
+            let column = format!("{:>1$} ", CONTINUATION_MARKER, NUMBER_WIDTH - 1);
+            self.write_marker(&column, gutter.as_ref())?;
+        }
+        self.write_gutter_rest(None)
+    }
+
+    fn write_cat_prefix(
+        &mut self,
+        number: Option<String>,
+        style: Option<&Style>,
+    ) -> io::Result<()> {
+        self.column = 0;
+        if let Some(number) = number {
+            self.write_marker(&number, style)?;
+            self.inner.write_all(b"\t")?;
+            self.column = (number.chars().count() / TAB_WIDTH + 1) * TAB_WIDTH;
+        }
+        self.text_start = self.column;
+        Ok(())
+    }
+
+    /// Writes the gutter after the number column.
+    fn write_gutter_rest(&mut self, change: Option<Change>) -> io::Result<()> {
+        let gutter = self.theme.as_ref().map(|t| t.gutter());
+        if self.options.decorations.changes {
+            match change {
+                Some(change) => {
+                    let (marker, key) = match change {
+                        Change::Added => ("+ ", "editorGutter.addedBackground"),
+                        Change::Modified => ("~ ", "editorGutter.modifiedBackground"),
+                        Change::RemovedAbove => ("‾ ", "editorGutter.deletedBackground"),
+                        Change::RemovedBelow => ("_ ", "editorGutter.deletedBackground"),
+                    };
+                    let style = self.theme.as_ref().map(|t| t.change_marker(key));
+                    self.write_marker(marker, style.as_ref())?;
+                }
+                None => self.inner.write_all(b"  ")?,
+            }
+        }
+        if self.options.decorations.grid {
+            self.write_marker("│ ", gutter.as_ref())?;
+        }
+        self.column = self.gutter_width() + if self.options.decorations.grid { 2 } else { 0 };
+        self.text_start = self.column;
+        Ok(())
+    }
+
+    /// Tells whether text of `width` columns has to go to the next row.
+    fn needs_break(&self, width: usize) -> bool {
+        self.wrap != Wrap::Never && width > 0 && self.column + width > self.width
+            && self.column > self.text_start
+    }
+
+    /// Ends the current row, filling the rest of a highlighted line with the
+    /// background.
+    fn end_row(&mut self) -> io::Result<()> {
+        if let Some(hl) = self.highlight.clone() {
+            // erasing the rest of the line fills it with the background
+            write!(self.inner, "{}\x1B[K{}", hl.color(), Style::reset())?;
+        }
+        self.inner.write_all(b"\n")?;
+        self.column = 0;
+        Ok(())
+    }
+
+    /// Wraps a line into the next row. `style` is the style of the text being
+    /// written, which is reset at the end of the row and restored after the
+    /// continuation prefix.
+    fn break_row(&mut self, style: Option<&Style>) -> io::Result<()> {
+        if style.is_some() {
+            self.inner.write_all(Style::reset().as_bytes())?;
+        }
+        self.end_row()?;
+        self.write_continuation()?;
+        if let Some(style) = style {
+            self.inner.write_all(style.color().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Returns the width of `bytes` written from the current column.
+    fn text_width(&self, bytes: &[u8]) -> usize {
+        let mut column = self.column;
+        let mut i = 0;
+        while i < bytes.len() {
+            let (_, len, width) = self.unit(bytes, i, column, true);
+            column += width;
+            i += len;
+        }
+        column - self.column
+    }
+
+    /// Returns the unit of text at `i`, which is a character or a byte with
+    /// its notation, as the notation, the number of bytes and the width on
+    /// the screen at `column`. Replacement characters are given as notations
+    /// to paint them when `marked` is true.
+    fn unit(
+        &self,
+        bytes: &[u8],
+        i: usize,
+        column: usize,
+        marked: bool,
+    ) -> (Option<String>, usize, usize) {
+        let b = bytes[i];
+        // the bytes of a replacement character have notations of `-v` too
+        if let Some(notation) = self.notation(b) {
+            let width = notation.len();
+            return (Some(notation), 1, width);
+        }
+        if marked && bytes[i..].starts_with(REPLACEMENT) {
+            return (Some("\u{FFFD}".to_owned()), REPLACEMENT.len(), 1);
+        }
+        match b {
+            // tabs are expanded from the start of the text for `--tabs`, and
+            // reach the tab stops of the terminal otherwise
+            b'\t' if self.options.tabs > 0 => {
+                let width = self.options.tabs - (column - self.text_start) % self.options.tabs;
+                (Some(" ".repeat(width)), 1, width)
+            }
+            b'\t' => (None, 1, TAB_WIDTH - column % TAB_WIDTH),
+            0..=31 | 127 => (None, 1, 0),
+            32..=126 => (None, 1, 1),
+            _ => {
+                let len = match b {
+                    0xC0..=0xDF => 2,
+                    0xE0..=0xEF => 3,
+                    0xF0..=0xF7 => 4,
+                    _ => 1,
+                };
+                let c = bytes
+                    .get(i..i + len)
+                    .and_then(|seq| str::from_utf8(seq).ok())
+                    .and_then(|seq| seq.chars().next());
+                match c {
+                    Some(c) => (None, len, c.width().unwrap_or(0)),
+                    // an invalid byte is shown as a replacement character
+                    None => (None, 1, 1),
+                }
+            }
+        }
+    }
+
+    /// Writes a horizontal border across the terminal, joined with the
+    /// vertical border of the gutter by `joint`.
+    fn write_rule(&mut self, joint: char) -> io::Result<()> {
+        let rule = if self.has_gutter() {
+            let width = self.gutter_width();
+            let left = "─".repeat(width);
+            let right = "─".repeat(self.width.saturating_sub(width + 1));
+            format!("{}{}{}", left, joint, right)
+        } else {
+            "─".repeat(self.width)
+        };
+        let style = self.theme.as_ref().map(|t| t.gutter());
+        self.write_marker(&rule, style.as_ref())?;
+        self.inner.write_all(b"\n")
+    }
+
+    /// Writes a message in place of the contents of an input.
+    pub fn write_notice(&mut self, msg: &str) -> Result<()> {
+        if !self.line_start {
+            self.inner.write_all(b"\n").map_err(Error::Output)?;
+            self.line_start = true;
+        }
+        writeln!(self.inner, "{}", msg).map_err(Error::Output)?;
+        self.inner.flush().map_err(Error::Output)
+    }
+
+    /// Writes `bytes` with control characters replaced by their notations,
+    /// wrapping it at the width of the terminal for `--wrap`. When `styles`
+    /// is given, the text is painted with the first style and the notations
+    /// and replacement characters with the second one.
+    fn write_text(&mut self, bytes: &[u8], styles: Option<(&Style, &Style)>) -> io::Result<()> {
+        if let Some((style, _)) = styles {
+            self.inner.write_all(style.color().as_bytes())?;
+        }
+
+        let mut plain_start = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let (notation, len, width) = self.unit(bytes, i, self.column, styles.is_some());
+            if self.needs_break(width) {
+                self.inner.write_all(&bytes[plain_start..i])?;
+                plain_start = i;
+                self.break_row(styles.map(|(style, _)| style))?;
+                // measured again on the new row, since tabs depend on the column
+                continue;
+            }
+            if let Some(notation) = notation {
+                self.inner.write_all(&bytes[plain_start..i])?;
+                match styles {
+                    // expanded tabs are painted like the text around them
+                    _ if bytes[i] == b'\t' && !self.options.show_tabs => {
+                        self.inner.write_all(notation.as_bytes())?
+                    }
+                    Some((style, marker)) => {
+                        self.write_marker(&notation, Some(marker))?;
+                        self.inner.write_all(style.color().as_bytes())?;
+                    }
+                    None => self.write_marker(&notation, None)?,
+                }
+                plain_start = i + len;
+            }
+            self.column += width;
+            i += len;
+        }
+        self.inner.write_all(&bytes[plain_start..])?;
+
+        if styles.is_some() {
+            self.inner.write_all(Style::reset().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn write_marker(&mut self, marker: &str, style: Option<&Style>) -> io::Result<()> {
+        match style {
+            Some(style) => write!(self.inner, "{}{}{}", style.color(), marker, Style::reset()),
+            None => self.inner.write_all(marker.as_bytes()),
+        }
+    }
+
+    /// Returns the notation of `-v` and `-T` for a byte, if it needs one.
+    fn notation(&self, b: u8) -> Option<String> {
+        match b {
+            b'\t' if self.options.show_tabs => Some("^I".to_owned()),
+            b'\t' => None,
+            _ if !self.options.show_nonprinting => None,
+            0..=31 => Some(format!("^{}", (b + 64) as char)),
+            32..=126 => None,
+            127 => Some("^?".to_owned()),
+            128..=159 => Some(format!("M-^{}", (b - 64) as char)),
+            160..=254 => Some(format!("M-{}", (b - 128) as char)),
+            255 => Some("M-^?".to_owned()),
+        }
+    }
+}
+
+/// Formats a size in bytes like `512 B` or `1.5 KiB`.
+fn human_size(size: u64) -> String {
+    let units = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < units.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, units[unit])
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use civet::{Binary, Decorations, Paging};
+    use lang::{LangLoader, SyntaxMapping};
+
+    pub fn options() -> Options {
+        Options {
+            display_number: false,
+            number_nonblank: false,
+            squeeze_blank: false,
+            show_ends: false,
+            show_tabs: false,
+            show_nonprinting: false,
+            colored: false,
+            print_supported: false,
+            list_languages: false,
+            language: None,
+            file_name: None,
+            guess: false,
+            encoding: None,
+            binary: Binary::Print,
+            line_ranges: LineRanges::default(),
+            highlight_lines: LineRanges::default(),
+            decorations: Decorations::default(),
+            follow: false,
+            compare: None,
+            recursive: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            tabs: 0,
+            wrap: Wrap::Never,
+            paging: Paging::Never,
+            syntax_mapping: SyntaxMapping::default(),
+            debug: false,
+            theme: theme::default(),
+        }
+    }
+
+    /// Writes inputs one after another like `cat`, colorized as Rust when
+    /// the output is colored, and returns the output without escape codes.
+    fn cat(options: &Options, inputs: &[&[u8]]) -> String {
+        let grammar = LangLoader::new().load_grammar("source.rust").unwrap();
+        let mut w = Writer::new(Vec::new(), options);
+        for input in inputs {
+            let mut lc = Some(LineColorizer::new(theme::load(options.theme), &grammar))
+                .filter(|_| options.colored);
+            w.write(*input, lc.as_mut(), &LineRanges::default()).unwrap();
+        }
+        let out = String::from_utf8_lossy(&w.into_inner()).into_owned();
+        strip_escapes(&out)
+    }
+
+    /// Writes an input as a Rust file to a terminal of `width` columns, and
+    /// returns the output with its escape codes.
+    fn write_file(options: &Options, width: usize, input: &[u8]) -> String {
+        let grammar = LangLoader::new().load_grammar("source.rust").unwrap();
+        let mut lc = Some(LineColorizer::new(theme::load(options.theme), &grammar))
+            .filter(|_| options.colored);
+        let mut w = Writer::new(Vec::new(), options);
+        w.width = width;
+        w.wrap = options.wrap;
+        let size = Some(Size::Bytes(input.len() as u64));
+        w.begin_input("a.rs", Some("Rust"), size, LineChanges::new()).unwrap();
+        w.write(input, lc.as_mut(), &options.line_ranges).unwrap();
+        w.end_input().unwrap();
+        String::from_utf8(w.into_inner()).unwrap()
+    }
+
+    pub fn strip_escapes(s: &str) -> String {
+        let mut out = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1B' {
+                // skips up to the final letter of the sequence
+                chars.by_ref().find(|c| c.is_ascii_alphabetic());
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn show_all() {
+        let mut options = options();
+        options.show_ends = true;
+        options.show_tabs = true;
+        options.show_nonprinting = true;
+        let input: &[u8] = b"a\tb\x01\x7f\xc3\xa9\xff\n";
+        // printf 'a\tb\x01\x7f\xc3\xa9\xff\n' | cat -A
+        assert_eq!(cat(&options, &[input]), "a^Ib^A^?M-CM-)M-^?$\n");
+
+        options.colored = true;
+        let input: &[u8] = b"let\tx\x01 = 1;\x7f\n";
+        assert_eq!(cat(&options, &[input]), "let^Ix^A = 1;^?$\n");
+    }
+
+    #[test]
+    fn show_nonprinting_colored() {
+        let mut options = options();
+        options.show_nonprinting = true;
+        // printf 'let s = "\xff\xc3\xa9\xef\xbf\xbd";\n' | cat -v
+        let input: &[u8] = b"let s = \"\xff\xc3\xa9\xef\xbf\xbd\";\n";
+        let expected = "let s = \"M-^?M-CM-)M-oM-?M-=\";\n";
+        assert_eq!(cat(&options, &[input]), expected);
+        options.colored = true;
+        assert_eq!(cat(&options, &[input]), expected);
+    }
+
+    #[test]
+    fn show_ends_and_tabs() {
+        let mut options = options();
+        options.show_ends = true;
+        assert_eq!(cat(&options, &[b"a\tb\r\n"]), "a\tb^M$\n");
+        options.show_ends = false;
+        options.show_tabs = true;
+        assert_eq!(cat(&options, &[b"a\tb\x01\n"]), "a^Ib\x01\n");
+    }
+
+    #[test]
+    fn numbers_across_files() {
+        let files: &[&[u8]] = &[b"x\n\n\n\ny\n", b"\n\nz\n"];
+        for &colored in &[false, true] {
+            let mut options = options();
+            options.colored = colored;
+            options.squeeze_blank = true;
+            options.display_number = true;
+            // cat -s -n
+            assert_eq!(
+                cat(&options, files),
+                "     1\tx\n     2\t\n     3\ty\n     4\t\n     5\tz\n"
+            );
+
+            let mut options = self::options();
+            options.colored = colored;
+            options.number_nonblank = true;
+            options.display_number = true;
+            // cat -b
+            assert_eq!(
+                cat(&options, files),
+                "     1\tx\n\n\n\n     2\ty\n\n\n     3\tz\n"
+            );
+        }
+    }
+
+    #[test]
+    fn line_continues_into_next_file() {
+        let mut options = options();
+        options.display_number = true;
+        // the number of a line is written once even when a file ends in it
+        assert_eq!(cat(&options, &[b"a", b"b\nc\n"]), "     1\tab\n     2\tc\n");
+    }
+
+    #[test]
+    fn input_in_parts() {
+        let options = options();
+        let mut lines = LineRanges::default();
+        lines.add("2:3").unwrap();
+        let mut w = Writer::new(Vec::new(), &options);
+        // like a followed file, whose appended lines are counted on
+        w.begin_input("a", None, None, LineChanges::new()).unwrap();
+        w.write(&b"a\nb\n"[..], None, &lines).unwrap();
+        w.write(&b"c\nd\n"[..], None, &lines).unwrap();
+        w.begin_input("b", None, None, LineChanges::new()).unwrap();
+        w.write(&b"e\nf\n"[..], None, &lines).unwrap();
+        assert_eq!(w.into_inner(), b"b\nc\nf\n");
+    }
+
+    #[test]
+    fn wrap_between_tokens() {
+        let mut options = options();
+        options.colored = true;
+        options.wrap = Wrap::Auto;
+        let input = b"let value = other(1);\n";
+        // a token which fits in a row is moved to the next row as a whole
+        let out = write_file(&options, 14, input);
+        assert_eq!(strip_escapes(&out), "let value = \nother(1);\n");
+        options.wrap = Wrap::Character;
+        let out = write_file(&options, 14, input);
+        assert_eq!(strip_escapes(&out), "let value = ot\nher(1);\n");
+        // the style of a token is reset at the end of a row and set again
+        // after the prefix of the next one
+        assert!(out.contains("\x1B[38;5;148mot\x1B[0m\n\x1B[38;5;148mher\x1B[0m"));
+    }
+
+    #[test]
+    fn wrap_characters() {
+        let mut options = options();
+        options.wrap = Wrap::Character;
+        // a wide character which does not fit goes to the next row
+        assert_eq!(write_file(&options, 7, "日本語の文字\n".as_bytes()), "日本語\nの文字\n");
+        // combining marks stay with the characters they follow
+        assert_eq!(
+            write_file(&options, 3, "a\u{301}bc\u{301}d\n".as_bytes()),
+            "a\u{301}bc\u{301}\nd\n"
+        );
+        // and notations are never split
+        options.show_nonprinting = true;
+        assert_eq!(write_file(&options, 3, b"ab\x01cd\n"), "ab\n^Ac\nd\n");
+    }
+
+    #[test]
+    fn continuation_marker() {
+        let mut options = options();
+        options.wrap = Wrap::Character;
+        options.display_number = true;
+        let input = b"let value = other(1);\n";
+        // the marker takes the place of the line number in its column
+        assert_eq!(
+            write_file(&options, 16, input),
+            "     1\tlet valu\n     ↪\te = othe\n     ↪\tr(1);\n"
+        );
+        options.display_number = false;
+        options.decorations.numbers = true;
+        options.decorations.grid = true;
+        assert_eq!(
+            write_file(&options, 20, input),
+            "──────┬─────────────\n    1 │ let value = \n    ↪ │ other(1);\n\
+             ──────┴─────────────\n"
+        );
+    }
+
+    #[test]
+    fn expand_tabs() {
+        let mut options = options();
+        options.tabs = 4;
+        options.display_number = true;
+        // tab stops are counted from the start of the text after the gutter
+        assert_eq!(write_file(&options, 80, b"a\tb\n\tc\n"), "     1\ta   b\n     2\t    c\n");
+        options.display_number = false;
+        options.decorations.numbers = true;
+        options.decorations.grid = true;
+        assert_eq!(
+            write_file(&options, 16, b"ab\tc\n"),
+            "──────┬─────────\n    1 │ ab  c\n──────┴─────────\n"
+        );
+
+        // without `--tabs`, tabs are written for the terminal to expand
+        let mut options = self::options();
+        assert_eq!(write_file(&options, 80, b"a\tb\n"), "a\tb\n");
+        options.colored = true;
+        assert_eq!(strip_escapes(&write_file(&options, 80, b"a\tb;\n")), "a\tb;\n");
+    }
+
+    #[test]
+    fn wrap_tabs() {
+        let mut options = options();
+        options.wrap = Wrap::Character;
+        // a tab crossing the edge goes to the next row, where it is measured
+        // again from the start of the row
+        assert_eq!(write_file(&options, 10, b"abcdefghi\tj\n"), "abcdefghi\n\tj\n");
+        options.tabs = 4;
+        assert_eq!(write_file(&options, 10, b"abcdefghi\tj\n"), "abcdefghi\n    j\n");
+        assert_eq!(write_file(&options, 10, b"abcdef\tj\n"), "abcdef  j\n");
+    }
+
+    #[test]
+    fn highlight_line() {
+        let mut options = options();
+        options.colored = true;
+        options.highlight_lines.add("1").unwrap();
+        // the background is set with the colors of the tokens, and erasing
+        // the rest of the row fills it
+        assert_eq!(
+            write_file(&options, 80, b"let x;\nlet y;\n"),
+            "\x1B[38;5;197;48;5;59mlet\x1B[0m\x1B[38;5;231;48;5;59m x;\x1B[0m\
+             \x1B[48;5;59m\x1B[K\x1B[0m\n\
+             \x1B[38;5;197mlet\x1B[0m\x1B[38;5;231m y;\x1B[0m\n"
+        );
+        // every row of a wrapped line is filled
+        options.wrap = Wrap::Character;
+        let out = write_file(&options, 4, b"let xyz;\n");
+        assert_eq!(strip_escapes(&out), "let \nxyz;\n");
+        assert_eq!(out.matches("\x1B[48;5;59m\x1B[K\x1B[0m\n").count(), 2);
+    }
+
+    #[test]
+    fn decorations() {
+        let mut options = options();
+        options.decorations = Decorations {
+            header: true,
+            numbers: true,
+            changes: false,
+            grid: true,
+        };
+        assert_eq!(
+            write_file(&options, 30, b"fn main() {}\n\nx\n"),
+            "──────┬───────────────────────\n      │ File: a.rs (Rust, 16 B)\n\
+             ──────┼───────────────────────\n    1 │ fn main() {}\n    2 │ \n    3 │ x\n\
+             ──────┴───────────────────────\n"
+        );
+        options.decorations.numbers = false;
+        options.decorations.grid = false;
+        // a last line without a newline is ended before the bottom border
+        assert_eq!(
+            write_file(&options, 30, b"fn main() {}\n\nx"),
+            "File: a.rs (Rust, 15 B)\nfn main() {}\n\nx\n"
+        );
+        options.colored = true;
+        let out = write_file(&options, 30, b"x\n");
+        assert!(out.starts_with("File: \x1B[1ma.rs\x1B[0m (Rust, 2 B)\n"));
+
+        // the contents are written as they are in the plain style
+        let options = self::options();
+        assert_eq!(write_file(&options, 30, b"fn main() {}\n\nx"), "fn main() {}\n\nx");
+    }
+}